@@ -0,0 +1,75 @@
+/// Heading of an ant, in clockwise order starting upwards.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    U, R, D, L
+}
+
+impl Direction {
+    pub fn turn(self, turn: Turn) -> Direction {
+        let quarters = match turn {
+            Turn::None => 0,
+            Turn::Right => 1,
+            Turn::UTurn => 2,
+            Turn::Left => 3,
+        };
+        match (self as usize + quarters) % 4 {
+            0 => Direction::U,
+            1 => Direction::R,
+            2 => Direction::D,
+            _ => Direction::L,
+        }
+    }
+
+    /// Cell offset of one step, `y` growing upwards.
+    pub fn delta(self) -> (i64, i64) {
+        match self {
+            Direction::U => (0, 1),
+            Direction::R => (1, 0),
+            Direction::D => (0, -1),
+            Direction::L => (-1, 0),
+        }
+    }
+}
+
+/// Relative turn of a turmite transition, `N`, `R`, `U` and `L` in rule strings.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Turn {
+    None,
+    Right,
+    UTurn,
+    Left,
+}
+
+impl Turn {
+    pub fn from_char(c: char) -> Option<Turn> {
+        match c.to_ascii_uppercase() {
+            'N' => Some(Turn::None),
+            'R' => Some(Turn::Right),
+            'U' => Some(Turn::UTurn),
+            'L' => Some(Turn::Left),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ant {
+    pub x: usize,
+    pub y: usize,
+    pub head: Direction,
+    pub state: usize,
+    /// Set once a bounded grid stops the ant at its edge.
+    pub halted: bool,
+}
+
+impl Ant {
+    pub fn new(x: usize, y: usize, head: Direction) -> Self {
+        Ant {
+            x,
+            y,
+            head,
+            state: 0,
+            halted: false,
+        }
+    }
+}
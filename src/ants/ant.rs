@@ -1,5 +0,0 @@
-use nannou::prelude::*;
-
-struct Ant {
-  
-}
\ No newline at end of file
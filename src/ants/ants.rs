@@ -1,13 +1,15 @@
 use nannou::prelude::*;
+use doom_fire::ant::{Ant, Direction};
+use doom_fire::pixels::{PixelBuffer, PixelTexture};
+use doom_fire::turmite::{cell_rgb, Edges, Rule, TurmiteGrid};
 
-const N: u32 = 50;
-const H_N: u32 = N / 2;
-const NN: u32 = N*N;
-const SCL: f32 = 10.0;
+const N: usize = 160;
+const H_N: usize = N / 2;
+const SCL: f32 = 5.0;
+const MAX_STEPS_PER_FRAME: u64 = 100_000;
 
-const H_SCL: f32 = SCL * 0.5;
 const SIZE: f32 = N as f32 * SCL;
-const H_SIZE: f32 = SIZE * 0.5;
+const ANT_COLOR: [u8; 4] = [220, 40, 40, 255];
 
 fn main() {
     nannou::app(model)
@@ -15,66 +17,95 @@ fn main() {
         .run();
 }
 
-fn real_coords(x: u32, y: u32) -> Vector2<f32> {
-    let r_x = x as f32 * SCL - H_SIZE + H_SCL;
-    let r_y = y as f32 * SCL - H_SIZE + H_SCL;
-    vec2(r_x, r_y)
-}
-
-fn x_y_to_index(x: u32, y: u32) -> usize {
-    (x + y * N) as usize
-}
-
-enum Direction {
-    U, D, L, R
-}
-
 struct Model {
-    grid: [f32; NN as usize],
-    head: Direction,
-    position: Vector2<u32>
+    grid: TurmiteGrid,
+    steps_per_frame: u64,
+    paused: bool,
+    pixels: PixelBuffer,
+    texture: PixelTexture,
 }
 
 fn model(app: &App) -> Model {
-    let _window = app
+    let window = app
         .new_window()
         .size(SIZE as u32, SIZE as u32)
         .view(view)
+        .key_pressed(key_pressed)
         .build()
         .unwrap();
-    let grid = [1.0; NN as usize];
+
+    // Usage: ants [rule] [ants], the rule being a turn string like RLR or a
+    // Pegg table like "{{{1,2,0},{0,8,0}}}"
+    let rule_text = std::env::args().nth(1).unwrap_or_else(|| "RL".to_string());
+    let rule = rule_text.parse::<Rule>().unwrap_or_else(|error| {
+        eprintln!("Invalid rule {:?}: {}", rule_text, error);
+        Rule::from_turns("RL").unwrap()
+    });
+    let count = std::env::args()
+        .nth(2)
+        .and_then(|arg| arg.parse::<usize>().ok())
+        .unwrap_or(1);
+
+    let mut grid = TurmiteGrid::new(N, N, rule, Edges::Toroidal);
+    let heads = [Direction::R, Direction::U, Direction::L, Direction::D];
+    for i in 0..count {
+        // Extra ants start on a small circle around the centre
+        let a = i as f32 / count as f32 * TAU;
+        let r = if count > 1 { N as f32 * 0.15 } else { 0.0 };
+        let x = (H_N as f32 + r * a.cos()) as usize;
+        let y = (H_N as f32 + r * a.sin()) as usize;
+        grid.add_ant(Ant::new(x, y, heads[i % heads.len()]));
+    }
+
     Model {
         grid,
-        head: Direction::R,
-        position: vec2(H_N, H_N)
+        steps_per_frame: 1,
+        paused: false,
+        pixels: PixelBuffer::new(N, N),
+        texture: PixelTexture::new(&app.window(window).unwrap(), N, N),
     }
 }
 
-fn resolve(x: u32, y: u32, state: f32, from: & Direction) -> (u32, u32, Direction, f32) {
-    let to = match from {
-        Direction::U   => if state == 1.0 && x < N - 1  { Direction::R } else { Direction::L },
-        Direction::R   => if state == 1.0 && y < N - 1 { Direction::D } else { Direction::U },
-        Direction::D   => if state == 1.0 && x > 0 { Direction::L } else { Direction::R },
-        _   => if state == 1.0 && y > 0 { Direction::U } else { Direction::D }
-    };
-    
-    let (nx, ny): (u32, u32) = match to {
-        Direction::U   => (x, y + 1),
-        Direction::R   => (x + 1, y),
-        Direction::D   => (x - 1, y),
-        _ => (x, y - 1)
-    };
-    (nx, ny, to, if state == 1.0 { 0.0 } else { 1.0 })
+fn key_pressed(_app: &App, model: &mut Model, key: Key) {
+    match key {
+        Key::E => {
+            let edges = match model.grid.edges() {
+                Edges::Toroidal => Edges::Bounded,
+                Edges::Bounded => Edges::Toroidal,
+            };
+            println!("{:?} edges", edges);
+            model.grid.set_edges(edges);
+        }
+        Key::Up => {
+            model.steps_per_frame = (model.steps_per_frame * 10).min(MAX_STEPS_PER_FRAME);
+        }
+        Key::Down => {
+            model.steps_per_frame = (model.steps_per_frame / 10).max(1);
+        }
+        Key::Space => {
+            model.paused = !model.paused;
+        }
+        Key::Right => {
+            if model.paused {
+                model.grid.step();
+            }
+        }
+        _other_key => {}
+    }
 }
 
 fn update(_app: &App, model: &mut Model, _update: Update) {
-    let p = model.position;
-    let idx = x_y_to_index(p.x, p.y);
-    let state = model.grid[idx];
-    let (n_x, n_y, n_head, n_state) = resolve(p.x, p.y, state, & model.head);
-    model.grid[idx] = n_state;
-    model.position = vec2(n_x, n_y);
-    model.head = n_head;
+    if !model.paused {
+        model.grid.run(model.steps_per_frame);
+    }
+    let colors = model.grid.rule().colors();
+    model.pixels.fill_from(model.grid.cells(), |&color| {
+        let [r, g, b] = cell_rgb(color, colors);
+        [r, g, b, 255]
+    });
+    for ant in model.grid.ants().iter() {
+        model.pixels.set(ant.x, ant.y, ANT_COLOR);
+    }
 }
 
 fn view(app: &App, model: &Model, frame: Frame){
@@ -82,13 +113,12 @@ fn view(app: &App, model: &Model, frame: Frame){
     if app.elapsed_frames() == 1 {
         draw.background().color(WHITE);
     }
-    let grid = model.grid;
-    for x in 0..N as u32 {
-        for y in 0..N as u32 {
-            let state = grid[x_y_to_index(x, y)];
-            draw.rect().xy(real_coords(x, y)).w_h(SCL, SCL).color(srgb(state, state, state));
-        }
-
-    }
+    model.texture.upload(app, &frame, &model.pixels);
+    model.texture.draw(&draw, Rect::from_w_h(SIZE, SIZE));
+    let status = if model.paused { " (paused)" } else { "" };
+    let text = format!("Step {}, {} per frame{}", model.grid.steps(), model.steps_per_frame, status);
+    let top_left = pt2(-SIZE * 0.5 + 130.0, SIZE * 0.5 - 15.0);
+    draw.rect().xy(top_left).w_h(260.0, 30.0).color(srgba(0.0, 0.0, 0.0, 0.6));
+    draw.text(&text).xy(top_left).w(250.0).left_justify().color(WHITE);
     draw.to_frame(app, &frame).unwrap();
-}
\ No newline at end of file
+}
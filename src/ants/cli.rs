@@ -0,0 +1,242 @@
+use nannou::image::{self, gif::GifEncoder, Delay, Frame, ImageResult, RgbaImage};
+use doom_fire::ant::{Ant, Direction};
+use doom_fire::args::{exit_with_usage, parse_value, Args};
+use doom_fire::turmite::{cell_rgb, Edges, Rule, TurmiteGrid};
+use std::fs::File;
+use std::path::Path;
+use std::process;
+
+const USAGE: &str = "Usage: turmite --steps <n> --out <file.png> [options]
+
+Runs turmites on a grid without opening a window and writes the final grid.
+
+Options:
+  --rule <rule>        turn string like RL or LLRR, or a Pegg table (default RL)
+  --size <w>[x<h>]     grid size in cells (default 200)
+  --steps <n>          number of steps to run
+  --start <x>,<y>      starting cell of an ant facing right, repeat for more
+                       ants (default the centre)
+  --bounded            halt ants at the edges instead of wrapping around
+  --scale <n>          pixels per cell in the images (default 1)
+  --out <file.png>     final grid
+  --gif <file.gif>     also record an animation
+  --every <n>          steps between animation frames (default 100)
+  --delay <ms>         delay between animation frames (default 50)";
+
+#[derive(Debug)]
+struct Options {
+    rule: Rule,
+    width: usize,
+    height: usize,
+    steps: u64,
+    starts: Vec<(usize, usize)>,
+    edges: Edges,
+    scale: u32,
+    out: String,
+    gif: Option<String>,
+    every: u64,
+    delay: u32,
+}
+
+/// Reads the options after the program name, `--help` having been handled.
+fn parse_options<I: Iterator<Item = String>>(mut args: Args<I>) -> Result<Options, String> {
+    let mut rule = Rule::from_turns("RL").unwrap();
+    let (mut width, mut height) = (200, 200);
+    let mut steps = None;
+    let mut starts = Vec::new();
+    let mut edges = Edges::Toroidal;
+    let mut scale = 1;
+    let mut out = None;
+    let mut gif = None;
+    let mut every = 100;
+    let mut delay = 50;
+
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--bounded" => edges = Edges::Bounded,
+            "--rule" => {
+                let value = args.value(&flag)?;
+                rule = value
+                    .parse::<Rule>()
+                    .map_err(|error| format!("Invalid rule {:?}: {}", value, error))?;
+            }
+            "--size" => {
+                let value = args.value(&flag)?;
+                let mut parts = value.splitn(2, 'x');
+                width = parse_value(&flag, parts.next().unwrap())?;
+                height = match parts.next() {
+                    Some(h) => parse_value(&flag, h)?,
+                    None => width,
+                };
+            }
+            "--steps" => steps = Some(args.parse(&flag)?),
+            "--start" => {
+                let value = args.value(&flag)?;
+                let mut parts = value.splitn(2, ',');
+                let x = parse_value(&flag, parts.next().unwrap())?;
+                let y = parse_value(&flag, parts.next().unwrap_or(""))?;
+                starts.push((x, y));
+            }
+            "--scale" => scale = args.parse(&flag)?,
+            "--out" => out = Some(args.value(&flag)?),
+            "--gif" => gif = Some(args.value(&flag)?),
+            "--every" => every = args.parse(&flag)?,
+            "--delay" => delay = args.parse(&flag)?,
+            other => return Err(format!("Unknown option {}", other)),
+        }
+    }
+
+    if width == 0 || height == 0 || scale == 0 || every == 0 {
+        return Err("Sizes, scale and frame interval must be positive".to_string());
+    }
+    if starts.is_empty() {
+        starts.push((width / 2, height / 2));
+    }
+    if let Some(&(x, y)) = starts.iter().find(|&&(x, y)| x >= width || y >= height) {
+        return Err(format!("Start {},{} is outside the {}x{} grid", x, y, width, height));
+    }
+    Ok(Options {
+        rule,
+        width,
+        height,
+        steps: steps.ok_or("Missing --steps")?,
+        starts,
+        edges,
+        scale,
+        out: out.ok_or("Missing --out")?,
+        gif,
+        every,
+        delay,
+    })
+}
+
+/// Grid of the options with an ant facing right on every start.
+fn new_grid(options: &Options) -> TurmiteGrid {
+    let mut grid = TurmiteGrid::new(options.width, options.height, options.rule.clone(), options.edges);
+    for &(x, y) in options.starts.iter() {
+        grid.add_ant(Ant::new(x, y, Direction::R));
+    }
+    grid
+}
+
+/// Renders the grid with row 0 at the bottom of the image.
+fn render(grid: &TurmiteGrid, scale: u32) -> RgbaImage {
+    let colors = grid.rule().colors();
+    let (w, h) = (grid.width() as u32, grid.height() as u32);
+    RgbaImage::from_fn(w * scale, h * scale, |px, py| {
+        let x = (px / scale) as usize;
+        let y = (h - 1 - py / scale) as usize;
+        let [r, g, b] = cell_rgb(grid.cell(x, y), colors);
+        image::Rgba([r, g, b, 255])
+    })
+}
+
+fn run(options: &Options) -> ImageResult<()> {
+    let mut grid = new_grid(options);
+    let mut encoder = match &options.gif {
+        Some(path) => Some(GifEncoder::new(File::create(path)?)),
+        None => None,
+    };
+    let delay = Delay::from_numer_denom_ms(options.delay, 1);
+    while grid.steps() < options.steps {
+        if let Some(encoder) = encoder.as_mut() {
+            if grid.steps() % options.every == 0 {
+                encoder.encode_frame(Frame::from_parts(render(&grid, options.scale), 0, 0, delay))?;
+            }
+        }
+        grid.step();
+    }
+    if let Some(encoder) = encoder.as_mut() {
+        encoder.encode_frame(Frame::from_parts(render(&grid, options.scale), 0, 0, delay))?;
+    }
+
+    render(&grid, options.scale).save(Path::new(&options.out))?;
+    let halted = grid.ants().iter().filter(|ant| ant.halted).count();
+    println!(
+        "Ran {} steps with {} ants ({} halted), wrote {}",
+        grid.steps(),
+        grid.ants().len(),
+        halted,
+        options.out
+    );
+    Ok(())
+}
+
+fn main() {
+    if std::env::args().skip(1).any(|arg| arg == "--help" || arg == "-h") {
+        println!("{}", USAGE);
+        process::exit(0);
+    }
+    let options = parse_options(Args::from_env()).unwrap_or_else(|message| exit_with_usage(&message, USAGE));
+    if let Err(error) = run(&options) {
+        eprintln!("Could not write images: {}", error);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Options, String> {
+        let args: Vec<String> = args.split_whitespace().map(str::to_string).collect();
+        parse_options(Args::new(args.into_iter()))
+    }
+
+    #[test]
+    fn defaults_put_one_ant_in_the_middle() {
+        let options = parse("--steps 10 --out grid.png").unwrap();
+        assert_eq!((options.width, options.height, options.steps), (200, 200, 10));
+        assert_eq!(options.starts, vec![(100, 100)]);
+        assert_eq!(options.edges, Edges::Toroidal);
+        assert_eq!(options.rule, Rule::from_turns("RL").unwrap());
+        assert_eq!(options.out, "grid.png");
+        assert_eq!(options.gif, None);
+    }
+
+    #[test]
+    fn starts_and_edges_reach_the_grid() {
+        let options = parse("--size 30x20 --start 3,4 --start 29,19 --bounded --steps 1 --out a.png").unwrap();
+        assert_eq!((options.width, options.height), (30, 20));
+        let grid = new_grid(&options);
+        assert_eq!(grid.edges(), Edges::Bounded);
+        assert_eq!(grid.ants(), &[Ant::new(3, 4, Direction::R), Ant::new(29, 19, Direction::R)][..]);
+    }
+
+    #[test]
+    fn bad_arguments_are_rejected() {
+        let bad = [
+            ("--out a.png", "Missing --steps"),
+            ("--steps 10", "Missing --out"),
+            ("--steps 10 --out", "Missing value for --out"),
+            ("--steps ten --out a.png", "Invalid value \"ten\" for --steps"),
+            ("--steps 10 --out a.png --size 0", "must be positive"),
+            ("--steps 10 --out a.png --size 20x", "Invalid value \"\" for --size"),
+            ("--steps 10 --out a.png --start 3", "Invalid value \"\" for --start"),
+            ("--steps 10 --out a.png --size 20 --start 20,0", "Start 20,0 is outside the 20x20 grid"),
+            ("--steps 10 --out a.png --rule RX", "Invalid rule \"RX\""),
+            ("--steps 10 --out a.png --wrap", "Unknown option --wrap"),
+        ];
+        for &(args, error) in bad.iter() {
+            match parse(args) {
+                Ok(options) => panic!("{:?} gave {:?}", args, options),
+                Err(message) => assert!(message.contains(error), "{:?} gave {:?}", args, message),
+            }
+        }
+    }
+
+    #[test]
+    fn images_have_row_0_at_the_bottom() {
+        let mut grid = TurmiteGrid::new(3, 2, Rule::from_turns("RL").unwrap(), Edges::Toroidal);
+        // Facing right on (0, 0), the ant paints it and turns down
+        grid.add_ant(Ant::new(0, 0, Direction::R));
+        grid.step();
+        let image = render(&grid, 2);
+        assert_eq!(image.dimensions(), (6, 4));
+        for (px, py, pixel) in image.enumerate_pixels() {
+            let painted = px < 2 && py >= 2;
+            let expected = if painted { [0, 0, 0, 255] } else { [255, 255, 255, 255] };
+            assert_eq!(pixel.0, expected, "pixel {},{}", px, py);
+        }
+    }
+}
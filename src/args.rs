@@ -0,0 +1,86 @@
+use std::env;
+use std::iter::Skip;
+use std::process;
+use std::str::FromStr;
+
+/// Command line arguments of the example binaries, read flag by flag. The
+/// errors are messages for `exit_with_usage`.
+pub struct Args<I> {
+    args: I,
+}
+
+impl Args<Skip<env::Args>> {
+    /// Arguments of the running program, without its name.
+    pub fn from_env() -> Self {
+        Args::new(env::args().skip(1))
+    }
+}
+
+impl<I: Iterator<Item = String>> Args<I> {
+    pub fn new(args: I) -> Self {
+        Args { args }
+    }
+
+    /// Argument following `flag`.
+    pub fn value(&mut self, flag: &str) -> Result<String, String> {
+        self.args.next().ok_or_else(|| format!("Missing value for {}", flag))
+    }
+
+    /// Argument following `flag`, parsed.
+    pub fn parse<T: FromStr>(&mut self, flag: &str) -> Result<T, String> {
+        let value = self.value(flag)?;
+        parse_value(flag, &value)
+    }
+}
+
+impl<I: Iterator<Item = String>> Iterator for Args<I> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        self.args.next()
+    }
+}
+
+/// `value` given to `flag`, parsed.
+pub fn parse_value<T: FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse::<T>()
+        .map_err(|_| format!("Invalid value {:?} for {}", value, flag))
+}
+
+/// Prints `message` followed by the usage and exits with status 2.
+pub fn exit_with_usage(message: &str, usage: &str) -> ! {
+    eprintln!("{}\n\n{}", message, usage);
+    process::exit(2);
+}
+
+/// Value of `result`, or its error given to `exit_with_usage`.
+pub fn or_exit<T>(result: Result<T, String>, usage: &str) -> T {
+    result.unwrap_or_else(|message| exit_with_usage(&message, usage))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Args<std::vec::IntoIter<String>> {
+        Args::new(list.iter().map(|s| s.to_string()).collect::<Vec<_>>().into_iter())
+    }
+
+    #[test]
+    fn flags_take_the_next_argument() {
+        let mut args = args(&["--steps", "12", "--out"]);
+        assert_eq!(args.next().as_deref(), Some("--steps"));
+        assert_eq!(args.parse::<u32>("--steps"), Ok(12));
+        assert_eq!(args.next().as_deref(), Some("--out"));
+        assert_eq!(args.value("--out"), Err("Missing value for --out".to_string()));
+    }
+
+    #[test]
+    fn invalid_values_name_the_flag() {
+        assert_eq!(
+            parse_value::<u32>("--steps", "-3"),
+            Err("Invalid value \"-3\" for --steps".to_string())
+        );
+    }
+}
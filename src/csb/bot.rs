@@ -0,0 +1,326 @@
+use crate::csb::physics::{self, Action, Thrust, Vector};
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const FIRST_TURN_TIMEOUT: Duration = Duration::from_millis(1000);
+pub const TURN_TIMEOUT: Duration = Duration::from_millis(75);
+
+/// A pod as bots see it each turn, the numbers of the game's input lines
+/// and the checkpoints it passed, which the game leaves to bots to count.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PodState {
+    pub x: i32,
+    pub y: i32,
+    pub vx: i32,
+    pub vy: i32,
+    pub angle: i32,
+    pub next_checkpoint: usize,
+    pub passed: usize,
+}
+
+impl PodState {
+    pub fn new(pod: &physics::Pod, next_checkpoint: usize, passed: usize) -> Self {
+        PodState {
+            x: pod.position.x as i32,
+            y: pod.position.y as i32,
+            vx: pod.velocity.x as i32,
+            vy: pod.velocity.y as i32,
+            angle: pod.angle.round() as i32,
+            next_checkpoint,
+            passed,
+        }
+    }
+
+    pub fn position(&self) -> Vector {
+        Vector::new(self.x as f64, self.y as f64)
+    }
+
+    pub fn velocity(&self) -> Vector {
+        Vector::new(self.vx as f64, self.vy as f64)
+    }
+}
+
+/// The game's input line, without `passed`.
+impl fmt::Display for PodState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {} {} {} {}", self.x, self.y, self.vx, self.vy, self.angle, self.next_checkpoint)
+    }
+}
+
+/// A player controlling one or more pods. `play` answers one action per
+/// pod in `own`, errors disqualify the player.
+pub trait Bot {
+    fn name(&self) -> String;
+
+    fn init(&mut self, _laps: usize, _checkpoints: &[Vector]) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn play(&mut self, own: &[PodState], opponents: &[PodState]) -> Result<Vec<Action>, String>;
+}
+
+/// Aims at `target` corrected for the current drift, slowing down while
+/// facing away from it.
+pub fn seek(pod: &PodState, target: Vector) -> Action {
+    let aim = target - pod.velocity() * 3.0;
+    let mut body = physics::Pod::new(pod.position());
+    body.angle = pod.angle as f64;
+    let off = if pod.angle < 0 { 0.0 } else { body.diff_angle(aim).abs() };
+    let thrust = if off > 90.0 { 0 } else { (100.0 * (1.0 - off / 90.0)) as i32 };
+    Action { target: aim, thrust: Thrust::Power(thrust) }
+}
+
+/// Every pod heads for its next checkpoint with `seek`.
+#[derive(Default)]
+pub struct SeekBot {
+    checkpoints: Vec<Vector>,
+}
+
+impl Bot for SeekBot {
+    fn name(&self) -> String {
+        "seek".to_string()
+    }
+
+    fn init(&mut self, _laps: usize, checkpoints: &[Vector]) -> Result<(), String> {
+        self.checkpoints = checkpoints.to_vec();
+        Ok(())
+    }
+
+    fn play(&mut self, own: &[PodState], _opponents: &[PodState]) -> Result<Vec<Action>, String> {
+        Ok(own.iter().map(|pod| seek(pod, self.checkpoints[pod.next_checkpoint])).collect())
+    }
+}
+
+/// First pod races like `SeekBot`, the others hunt the leading opponent,
+/// the one with the most checkpoints passed and then the closest to its
+/// next one, waiting at the checkpoint after it when too far.
+#[derive(Default)]
+pub struct BlockerBot {
+    checkpoints: Vec<Vector>,
+}
+
+/// Distance under which a blocker goes for the pod itself.
+const CHASE_DISTANCE: f64 = 3000.0;
+
+impl BlockerBot {
+    fn block(&self, pod: &PodState, opponents: &[PodState]) -> Action {
+        let leader = opponents.iter().max_by(|a, b| {
+            let da = a.position().distance(self.checkpoints[a.next_checkpoint]);
+            let db = b.position().distance(self.checkpoints[b.next_checkpoint]);
+            a.passed.cmp(&b.passed).then(db.partial_cmp(&da).unwrap())
+        });
+        let leader = match leader {
+            Some(leader) => leader,
+            None => return seek(pod, self.checkpoints[pod.next_checkpoint]),
+        };
+        let next = leader.position() + leader.velocity();
+        if pod.position().distance(leader.position()) > CHASE_DISTANCE {
+            let ahead = self.checkpoints[(leader.next_checkpoint + 1) % self.checkpoints.len()];
+            return seek(pod, ahead);
+        }
+        // Shield when both pods will touch after this turn
+        let me = pod.position() + pod.velocity();
+        let thrust = if me.distance(next) < 2.0 * physics::POD_RADIUS {
+            Thrust::Shield
+        } else {
+            Thrust::Power(physics::MAX_THRUST)
+        };
+        Action { target: next + leader.velocity() - pod.velocity(), thrust }
+    }
+}
+
+impl Bot for BlockerBot {
+    fn name(&self) -> String {
+        "blocker".to_string()
+    }
+
+    fn init(&mut self, _laps: usize, checkpoints: &[Vector]) -> Result<(), String> {
+        self.checkpoints = checkpoints.to_vec();
+        Ok(())
+    }
+
+    fn play(&mut self, own: &[PodState], opponents: &[PodState]) -> Result<Vec<Action>, String> {
+        Ok(own
+            .iter()
+            .enumerate()
+            .map(|(i, pod)| {
+                if i == 0 {
+                    seek(pod, self.checkpoints[pod.next_checkpoint])
+                } else {
+                    self.block(pod, opponents)
+                }
+            })
+            .collect())
+    }
+}
+
+/// External program playing through the CodinGame protocol: it reads the
+/// laps, the checkpoints and then every turn its pods followed by the
+/// opponents', and must answer `x y thrust` per pod within the time limit.
+pub struct ProcessBot {
+    command: String,
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    first_turn: bool,
+}
+
+impl ProcessBot {
+    pub fn spawn(command: &str) -> io::Result<Self> {
+        let mut parts = command.split_whitespace();
+        let program = parts
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty bot command"))?;
+        let mut child = Command::new(program)
+            .args(parts)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+
+        // Reading on a thread lets the referee wait on the channel with a timeout
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                match line {
+                    Ok(line) => {
+                        if sender.send(line).is_err() {
+                            break;
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+        });
+
+        Ok(ProcessBot {
+            command: command.to_string(),
+            child,
+            stdin,
+            lines,
+            first_turn: true,
+        })
+    }
+
+    fn send(&mut self, text: &str) -> Result<(), String> {
+        self.stdin
+            .write_all(text.as_bytes())
+            .and_then(|_| self.stdin.flush())
+            .map_err(|e| format!("{} stopped reading: {}", self.command, e))
+    }
+}
+
+fn parse_action(line: &str) -> Result<Action, String> {
+    let mut parts = line.split_whitespace();
+    let mut next = |what: &str| parts.next().ok_or_else(|| format!("missing {} in {:?}", what, line));
+    let x = next("x")?.parse::<i32>().map_err(|e| e.to_string())?;
+    let y = next("y")?.parse::<i32>().map_err(|e| e.to_string())?;
+    let thrust = next("thrust")?.parse::<Thrust>()?;
+    // Anything after the thrust is a message the game shows, ignored here
+    Ok(Action {
+        target: Vector::new(x as f64, y as f64),
+        thrust,
+    })
+}
+
+impl Bot for ProcessBot {
+    fn name(&self) -> String {
+        self.command.clone()
+    }
+
+    fn init(&mut self, laps: usize, checkpoints: &[Vector]) -> Result<(), String> {
+        let mut text = format!("{}\n{}\n", laps, checkpoints.len());
+        for c in checkpoints.iter() {
+            text += &format!("{} {}\n", c.x as i32, c.y as i32);
+        }
+        self.send(&text)
+    }
+
+    fn play(&mut self, own: &[PodState], opponents: &[PodState]) -> Result<Vec<Action>, String> {
+        let mut text = String::new();
+        for pod in own.iter().chain(opponents.iter()) {
+            text += &format!("{}\n", pod);
+        }
+        self.send(&text)?;
+
+        // The time limit covers the whole answer, not each of its lines
+        let timeout = if self.first_turn { FIRST_TURN_TIMEOUT } else { TURN_TIMEOUT };
+        let deadline = Instant::now() + timeout;
+        self.first_turn = false;
+        let mut actions = Vec::with_capacity(own.len());
+        for _ in own.iter() {
+            let line = self
+                .lines
+                .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                .map_err(|_| format!("{} timed out", self.command))?;
+            actions.push(parse_action(&line)?);
+        }
+        Ok(actions)
+    }
+}
+
+impl Drop for ProcessBot {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pod(x: i32, y: i32, next_checkpoint: usize, passed: usize) -> PodState {
+        PodState { x, y, vx: 0, vy: 0, angle: 0, next_checkpoint, passed }
+    }
+
+    #[test]
+    fn blocker_hunts_the_pod_furthest_in_the_race() {
+        let checkpoints = [
+            Vector::new(1000.0, 1000.0),
+            Vector::new(8000.0, 1000.0),
+            Vector::new(8000.0, 8000.0),
+        ];
+        let mut bot = BlockerBot::default();
+        bot.init(3, &checkpoints).unwrap();
+        let own = [pod(1000, 1000, 1, 0), pod(5000, 5000, 1, 0)];
+        // The second opponent is nearer its checkpoint but a lap behind
+        let opponents = [pod(5500, 5000, 1, 4), pod(4500, 5000, 2, 2)];
+        let actions = bot.play(&own, &opponents).unwrap();
+        assert_eq!(actions[1].target, opponents[0].position());
+    }
+
+    /// Answer of a shell script bot to its first turn with two pods, the
+    /// script running once it read them.
+    fn first_answer(name: &str, script: &str) -> Result<Vec<Action>, String> {
+        let path = std::env::temp_dir().join(format!("doom_fire_{}_{}.sh", std::process::id(), name));
+        std::fs::write(&path, format!("read own\nread other\n{}", script)).unwrap();
+        let mut bot = ProcessBot::spawn(&format!("sh {}", path.display())).unwrap();
+        let answer = bot.play(&[pod(0, 0, 1, 0), pod(0, 0, 1, 0)], &[]);
+        std::fs::remove_file(&path).unwrap();
+        answer
+    }
+
+    #[test]
+    fn process_bots_answer_every_pod_in_time() {
+        let actions = first_answer("quick", "echo 10 20 100\necho 30 40 BOOST\n").unwrap();
+        assert_eq!(actions[0].target, Vector::new(10.0, 20.0));
+        assert_eq!(actions[1].thrust, Thrust::Boost);
+
+        // Each line comes within the limit, the whole answer does not
+        let slow = "sleep 0.6\necho 10 20 100\nsleep 0.6\necho 30 40 100\n";
+        let error = first_answer("slow", slow).unwrap_err();
+        assert!(error.ends_with("timed out"), "{}", error);
+    }
+
+    #[test]
+    fn protocol_line_leaves_out_passed() {
+        assert_eq!(pod(1, 2, 3, 4).to_string(), "1 2 0 0 0 3");
+    }
+}
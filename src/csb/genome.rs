@@ -0,0 +1,296 @@
+use crate::csb::bot::{Bot, PodState};
+use crate::csb::map::Track;
+use crate::csb::physics::{Action, Thrust, Vector, MAX_ROTATION, MAX_THRUST};
+use crate::csb::referee::{Outcome, Referee};
+use crate::rng::Pcg32;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const INPUTS: usize = 6;
+const HIDDEN: usize = 8;
+const OUTPUTS: usize = 2;
+/// Hidden layer weights, whose bias is the one for the constant last input,
+/// then the output layer's with a bias column.
+pub const WEIGHTS: usize = HIDDEN * INPUTS + OUTPUTS * (HIDDEN + 1);
+/// Genomes drawn for each tournament.
+const TOURNAMENT: usize = 4;
+/// Worth of a checkpoint, more than any distance on the field.
+const CHECKPOINT_SCORE: f64 = 50000.0;
+
+/// Weights of a small network steering one pod: it sees the next two
+/// checkpoints and its velocity in its own frame, and answers a rotation
+/// and a thrust.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Genome {
+    pub weights: Vec<f64>,
+}
+
+fn signed(rng: &mut Pcg32) -> f64 {
+    rng.range_f32(-1.0, 1.0) as f64
+}
+
+/// Angle of `v` in degrees, clockwise like the game's.
+fn degrees(v: Vector) -> f64 {
+    v.y.atan2(v.x) * 180.0 / PI
+}
+
+/// `a` wrapped to `(-180, 180]`.
+fn wrap(a: f64) -> f64 {
+    let a = a.rem_euclid(360.0);
+    if a > 180.0 {
+        a - 360.0
+    } else {
+        a
+    }
+}
+
+impl Genome {
+    pub fn random(rng: &mut Pcg32) -> Self {
+        Genome {
+            weights: (0..WEIGHTS).map(|_| signed(rng)).collect(),
+        }
+    }
+
+    /// Takes every weight from either parent.
+    pub fn crossover(&self, other: &Genome, rng: &mut Pcg32) -> Genome {
+        let weights = self
+            .weights
+            .iter()
+            .zip(other.weights.iter())
+            .map(|(&a, &b)| if rng.below(2) == 0 { a } else { b })
+            .collect();
+        Genome { weights }
+    }
+
+    /// Nudges each weight with probability `rate` by up to `amount`.
+    pub fn mutate(&mut self, rng: &mut Pcg32, rate: f32, amount: f64) {
+        for w in self.weights.iter_mut() {
+            if rng.next_f32() < rate {
+                *w += signed(rng) * amount;
+            }
+        }
+    }
+
+    fn inputs(pod: &PodState, checkpoints: &[Vector]) -> [f64; INPUTS] {
+        let position = pod.position();
+        let next = checkpoints[pod.next_checkpoint];
+        let after = checkpoints[(pod.next_checkpoint + 1) % checkpoints.len()];
+        // Before the first turn the pod may face anywhere, so it faces the checkpoint
+        let heading = if pod.angle < 0 { degrees(next - position) } else { pod.angle as f64 };
+        let h = heading * PI / 180.0;
+        let (cos, sin) = (h.cos(), h.sin());
+        let v = pod.velocity();
+        [
+            wrap(degrees(next - position) - heading) / 180.0,
+            position.distance(next) / 10000.0,
+            wrap(degrees(after - next) - heading) / 180.0,
+            (v.x * cos + v.y * sin) / 1000.0,
+            (v.y * cos - v.x * sin) / 1000.0,
+            1.0,
+        ]
+    }
+
+    pub fn action(&self, pod: &PodState, checkpoints: &[Vector]) -> Action {
+        let inputs = Genome::inputs(pod, checkpoints);
+        let (hidden_weights, output_weights) = self.weights.split_at(HIDDEN * INPUTS);
+        let hidden: Vec<f64> = hidden_weights
+            .chunks(INPUTS)
+            .map(|row| row.iter().zip(inputs.iter()).map(|(w, x)| w * x).sum::<f64>().tanh())
+            .collect();
+        let outputs: Vec<f64> = output_weights
+            .chunks(HIDDEN + 1)
+            .map(|row| row[HIDDEN] + row.iter().zip(hidden.iter()).map(|(w, x)| w * x).sum::<f64>())
+            .collect();
+
+        let position = pod.position();
+        let next = checkpoints[pod.next_checkpoint];
+        let heading = if pod.angle < 0 { degrees(next - position) } else { pod.angle as f64 };
+        let a = (heading + outputs[0].tanh() * MAX_ROTATION) * PI / 180.0;
+        let target = position + Vector::new(a.cos(), a.sin()) * 10000.0;
+        let power = (1.0 / (1.0 + (-outputs[1]).exp())) * MAX_THRUST as f64;
+        Action {
+            target,
+            thrust: Thrust::Power(power.round() as i32),
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let json = fs::read_to_string(path)?;
+        let genome: Genome = serde_json::from_str(&json)?;
+        if genome.weights.len() != WEIGHTS {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("genome has {} weights, expected {}", genome.weights.len(), WEIGHTS),
+            ));
+        }
+        Ok(genome)
+    }
+}
+
+/// Every pod driven by the same genome.
+pub struct GenomeBot {
+    genome: Genome,
+    checkpoints: Vec<Vector>,
+}
+
+impl GenomeBot {
+    pub fn new(genome: Genome) -> Self {
+        GenomeBot {
+            genome,
+            checkpoints: Vec::new(),
+        }
+    }
+}
+
+impl Bot for GenomeBot {
+    fn name(&self) -> String {
+        "genome".to_string()
+    }
+
+    fn init(&mut self, _laps: usize, checkpoints: &[Vector]) -> Result<(), String> {
+        self.checkpoints = checkpoints.to_vec();
+        Ok(())
+    }
+
+    fn play(&mut self, own: &[PodState], _opponents: &[PodState]) -> Result<Vec<Action>, String> {
+        Ok(own.iter().map(|pod| self.genome.action(pod, &self.checkpoints)).collect())
+    }
+}
+
+/// Score of one pod racing alone: checkpoints passed and closeness to the
+/// next one, finishing early earning the turns left.
+pub fn race(genome: &Genome, track: &Track, turns: u32) -> f64 {
+    let mut referee = Referee::new(track.clone(), vec![Box::new(GenomeBot::new(genome.clone()))], 1);
+    while referee.outcome().is_none() && referee.turn() < turns {
+        referee.step();
+    }
+    let racer = &referee.racers()[0];
+    let next = track.checkpoints[racer.next_checkpoint];
+    let score = racer.passed as f64 * CHECKPOINT_SCORE - racer.pod.position.distance(next);
+    match referee.outcome() {
+        Some(Outcome::Finished { .. }) => score + (turns - referee.turn()) as f64 * 1000.0,
+        _ => score,
+    }
+}
+
+/// Mean score of `race` over the tracks.
+pub fn fitness(genome: &Genome, tracks: &[Track], turns: u32) -> f64 {
+    tracks.iter().map(|track| race(genome, track, turns)).sum::<f64>() / tracks.len() as f64
+}
+
+/// Best of a few random genomes, `scored` being sorted best first.
+pub fn tournament<'a>(scored: &'a [(Genome, f64)], rng: &mut Pcg32) -> &'a Genome {
+    let best = (0..TOURNAMENT)
+        .map(|_| rng.below(scored.len() as u32) as usize)
+        .min()
+        .unwrap();
+    &scored[best].0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("doom_fire_{}_{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn genomes_round_trip_through_json() {
+        let path = temp_path("genome.json");
+        let genome = Genome::random(&mut Pcg32::new(1));
+        genome.save(&path).unwrap();
+        assert_eq!(Genome::load(&path).unwrap(), genome);
+
+        let short = Genome {
+            weights: genome.weights[1..].to_vec(),
+        };
+        short.save(&path).unwrap();
+        assert_eq!(Genome::load(&path).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn breeding_is_deterministic_and_keeps_the_length() {
+        let breed = |seed: u64| {
+            let mut rng = Pcg32::new(seed);
+            let (a, b) = (Genome::random(&mut rng), Genome::random(&mut rng));
+            let mut child = a.crossover(&b, &mut rng);
+            child.mutate(&mut rng, 0.5, 0.1);
+            child
+        };
+        let child = breed(2);
+        assert_eq!(child.weights.len(), WEIGHTS);
+        assert_eq!(child, breed(2));
+        assert_ne!(child, breed(3));
+    }
+
+    #[test]
+    fn actions_stay_within_the_game_limits() {
+        let checkpoints = [Vector::new(3000.0, 2000.0), Vector::new(12000.0, 7000.0)];
+        let mut rng = Pcg32::new(4);
+        for _ in 0..200 {
+            // Large weights saturate the outputs both ways
+            let mut genome = Genome::random(&mut rng);
+            for w in genome.weights.iter_mut() {
+                *w *= 50.0;
+            }
+            let pod = PodState {
+                x: rng.below(16000) as i32,
+                y: rng.below(9000) as i32,
+                vx: rng.below(1200) as i32 - 600,
+                vy: rng.below(1200) as i32 - 600,
+                angle: rng.below(360) as i32,
+                next_checkpoint: rng.below(2) as usize,
+                passed: 0,
+            };
+            let action = genome.action(&pod, &checkpoints);
+            match action.thrust {
+                Thrust::Power(power) => assert!((0..=MAX_THRUST).contains(&power), "thrust {}", power),
+                other => panic!("{:?}", other),
+            }
+            let turn = wrap(degrees(action.target - pod.position()) - pod.angle as f64);
+            assert!(turn.abs() <= MAX_ROTATION + 1e-6, "turn {}", turn);
+        }
+    }
+
+    /// Turns as far as it may towards the next checkpoint at full thrust,
+    /// like the seek bot without its drift correction.
+    fn steering_genome() -> Genome {
+        let mut weights = vec![0.0; WEIGHTS];
+        // First hidden unit follows the angle to the checkpoint, the
+        // rotation output follows it and the thrust output is all bias
+        weights[0] = 10.0;
+        let rotation = HIDDEN * INPUTS;
+        let thrust = rotation + HIDDEN + 1;
+        weights[rotation] = 10.0;
+        weights[thrust + HIDDEN] = 10.0;
+        Genome { weights }
+    }
+
+    #[test]
+    fn steering_beats_doing_nothing() {
+        let tracks: Vec<Track> = (0..3).map(Track::official).chain((0..3).map(Track::random)).collect();
+        let zero = Genome {
+            weights: vec![0.0; WEIGHTS],
+        };
+        let (steering, idle) = (fitness(&steering_genome(), &tracks, 300), fitness(&zero, &tracks, 300));
+        assert!(steering > idle + CHECKPOINT_SCORE, "steering {} against {}", steering, idle);
+    }
+
+    #[test]
+    fn races_score_the_same_every_time() {
+        let genome = Genome::random(&mut Pcg32::new(5));
+        let tracks = [Track::official(1), Track::random(6)];
+        let scores: Vec<f64> = (0..3).map(|_| fitness(&genome, &tracks, 200)).collect();
+        assert_eq!(scores, vec![scores[0]; 3]);
+    }
+}
@@ -1,5 +1,14 @@
 use nannou::prelude::*;
 
+use doom_fire::args::{exit_with_usage, or_exit, Args};
+use doom_fire::csb::bot::{BlockerBot, Bot, ProcessBot, SeekBot};
+use doom_fire::csb::genome::{Genome, GenomeBot};
+use doom_fire::csb::map::{Track, OFFICIAL_MAPS};
+use doom_fire::csb::physics::{self, CHECKPOINT_RADIUS, POD_RADIUS};
+use doom_fire::csb::referee::{Outcome, Referee};
+use std::collections::VecDeque;
+use std::path::Path;
+
 const W: u32 = 16000;
 const H: u32 = 9000;
 const SCL: u32 = 20;
@@ -7,8 +16,20 @@ const W_SCL: u32 = W / SCL;
 const H_SCL: u32 = H / SCL;
 const H_W_SCL: i32 = W_SCL as i32 / 2;
 const H_H_SCL: i32 = H_SCL as i32 / 2;
-const CIRCLE:u32 = 600;
-const POD_RADIUS: u32 = 400 / SCL;
+const SAVE_PATH: &str = "track.txt";
+const USAGE: &str = "Usage: csb [seed] [--map <file>] [--official <n>] [--genome <file>] [--bot <command>]...
+
+Bot commands like \"python3 bot.py\" take the teams in order. The genome
+drives the first team, so it cannot be given along with a bot command.";
+const TEAMS: usize = 2;
+const PODS_PER_TEAM: usize = 2;
+/// Positions kept in each pod's trail.
+const TRAIL_LENGTH: usize = 80;
+/// Pod colours, a blue and a red team with a lighter runner.
+const POD_COLORS: [[(f32, f32, f32); PODS_PER_TEAM]; TEAMS] = [
+    [(0.45, 0.75, 1.0), (0.1, 0.3, 0.9)],
+    [(1.0, 0.55, 0.5), (0.85, 0.1, 0.1)],
+];
 
 fn main() {
     nannou::app(model)
@@ -16,48 +37,15 @@ fn main() {
         .run();
 }
 
-fn vec_len_squared(pt: Vector2) -> f32 {
-  pt.x * pt.x + pt.y + pt.y
-}
-
-struct Pod {
-    pos: Point2,
-    vel: Vector2,
-    target: Point2,
-    target_idx: usize,
-    total_targets: usize,
-}
-impl Pod {
-    pub fn new(pos: Point2, vel: Point2) -> Self {
-        Pod {
-            pos,
-            vel,
-            target: pt2(0.0, 0.0),
-            target_idx: 1,
-            total_targets: 4,
-        }
-    }
-    fn draw(&self, draw: &Draw) {
-        draw.ellipse().xy(from_cg_coords(self.pos.x, self.pos.y)).w_h(POD_RADIUS as f32, POD_RADIUS as f32).color(WHITE);
-    }
-    fn seek(&mut self) {
-        let desired = (self.target - self.pos).normalize() * 100.0;
-        let steering = (desired - self.vel).limit_magnitude(0.5); // divide by the mass
-        self.vel = (self.vel + steering).limit_magnitude(100.0)
-    }
-    fn update(&mut self) {
-        self.pos += self.vel * 0.5;
-        let pt_dist = self.target - self.pos;
-        if vec_len_squared(pt_dist) < 10000.0 {
-            self.target_idx = (self.target_idx + 1) % self.total_targets;
-            eprintln!("{}", self.target_idx);
-        }
-    }
-}
-
 struct Model {
-    track: Vec<Point2>,
-    pod: Pod,
+    /// External bot commands by team, built-in bots playing the others.
+    bot_commands: Vec<String>,
+    /// Trained by `csb_train`, drives the first team instead of the seek bot.
+    genome: Option<Genome>,
+    /// Official map shown, `None` for a generated or loaded track.
+    official: Option<usize>,
+    referee: Referee,
+    trails: Vec<VecDeque<Point2>>,
 }
 
 fn from_cg_coords(x: f32, y: f32) -> Point2 {
@@ -68,14 +56,8 @@ fn from_cg_coords(x: f32, y: f32) -> Point2 {
     pt2(r_x as f32, r_y as f32)
 }
 
-fn random_point() -> Point2 {
-    let x = random_range(100, W - 100) as i32;
-    let y = random_range(100, H - 100) as i32;
-    pt2(x as f32, y as f32)
-}
-
-fn draw_circle(draw: &Draw, pt: &Point2, radius: u32) {
-    let r = (radius / SCL) as f32;
+fn draw_circle(draw: &Draw, pt: &Point2, radius: f64) {
+    let r = 2.0 * radius as f32 / SCL as f32;
     let cg_coords = from_cg_coords(pt.x, pt.y);
     draw.ellipse().xy(cg_coords).w_h(r, r).color(WHITE);
 }
@@ -85,39 +67,222 @@ fn model(app: &App) -> Model {
         .new_window()
         .size(W_SCL, H_SCL)
         .view(view)
+        .key_pressed(key_pressed)
         .build()
         .unwrap();
-    let mut track = vec![];
-    for _i in 0..4 {
-        let pt = random_point();
-        track.push(pt);
+
+    let mut seed = None;
+    let mut map_path = None;
+    let mut official = None;
+    let mut bot_commands = Vec::new();
+    let mut genome_path = None;
+    let mut args = Args::from_env();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--map" => map_path = Some(or_exit(args.value(&arg), USAGE)),
+            "--official" => {
+                let index = or_exit(args.parse::<usize>(&arg), USAGE);
+                if index >= OFFICIAL_MAPS.len() {
+                    let message = format!("--official expects 0 to {}", OFFICIAL_MAPS.len() - 1);
+                    exit_with_usage(&message, USAGE);
+                }
+                official = Some(index);
+            }
+            "--bot" => bot_commands.push(or_exit(args.value(&arg), USAGE)),
+            "--genome" => genome_path = Some(or_exit(args.value(&arg), USAGE)),
+            other => match other.parse::<u64>() {
+                Ok(n) if seed.is_none() => seed = Some(n),
+                _ => exit_with_usage(&format!("Unknown argument {:?}", other), USAGE),
+            },
+        }
+    }
+    if genome_path.is_some() && !bot_commands.is_empty() {
+        exit_with_usage("--genome drives the first team, which the first --bot already takes", USAGE);
     }
+    let track = if let Some(path) = map_path {
+        Track::load(Path::new(&path)).unwrap_or_else(|error| {
+            eprintln!("Could not load {}: {}", path, error);
+            std::process::exit(1);
+        })
+    } else if let Some(index) = official {
+        Track::official(index)
+    } else {
+        let seed = seed.unwrap_or_else(random::<u64>);
+        println!("Seed {}", seed);
+        Track::random(seed)
+    };
 
-    let initial_pos = track[0].clone();
+    let genome = genome_path.map(|path| {
+        Genome::load(&path).unwrap_or_else(|error| {
+            eprintln!("Could not load {}: {}", path, error);
+            std::process::exit(1);
+        })
+    });
 
+    let referee = new_referee(&bot_commands, &genome, track);
     Model {
-        track,
-        pod: Pod::new(initial_pos, pt2(0.0, 0.0)),
+        bot_commands,
+        genome,
+        official,
+        trails: vec![VecDeque::new(); referee.racers().len()],
+        referee,
+    }
+}
+
+/// The first team runs the genome or the seek bot and the second one a
+/// runner and a blocker, unless commands replace them.
+fn new_referee(bot_commands: &[String], genome: &Option<Genome>, track: Track) -> Referee {
+    let bots = (0..TEAMS)
+        .map(|team| -> Box<dyn Bot> {
+            match bot_commands.get(team) {
+                Some(command) => Box::new(ProcessBot::spawn(command).unwrap_or_else(|error| {
+                    eprintln!("Could not start {}: {}", command, error);
+                    std::process::exit(1);
+                })),
+                None if team == 0 => match genome {
+                    Some(genome) => Box::new(GenomeBot::new(genome.clone())),
+                    None => Box::new(SeekBot::default()),
+                },
+                None => Box::new(BlockerBot::default()),
+            }
+        })
+        .collect();
+    Referee::new(track, bots, PODS_PER_TEAM)
+}
+
+fn key_pressed(_app: &App, model: &mut Model, key: Key) {
+    let track = match key {
+        Key::G => {
+            let seed = random::<u64>();
+            println!("Seed {}", seed);
+            model.official = None;
+            Track::random(seed)
+        }
+        Key::O => {
+            let index = model.official.map_or(0, |i| (i + 1) % OFFICIAL_MAPS.len());
+            model.official = Some(index);
+            Track::official(index)
+        }
+        Key::R => model.referee.track().clone(),
+        Key::S => {
+            match model.referee.track().save(Path::new(SAVE_PATH)) {
+                Ok(()) => println!("Saved {}", SAVE_PATH),
+                Err(error) => eprintln!("Could not save {}: {}", SAVE_PATH, error),
+            }
+            return;
+        }
+        _ => return,
+    };
+    model.referee = new_referee(&model.bot_commands, &model.genome, track);
+    for trail in model.trails.iter_mut() {
+        trail.clear();
     }
 }
 
 fn update(_app: &App, model: &mut Model, _update: Update) {
-    let pod = &mut model.pod;
-    //let target = to_cg_coords(app.mouse.x, app.mouse.y);
-    let target = model.track[pod.target_idx].clone();
-    pod.target = target;
-    pod.seek();
-    pod.update();
+    let was_over = model.referee.outcome().is_some();
+    model.referee.step();
+    for (trail, racer) in model.trails.iter_mut().zip(model.referee.racers()) {
+        trail.push_back(pt2(racer.pod.position.x as f32, racer.pod.position.y as f32));
+        if trail.len() > TRAIL_LENGTH {
+            trail.pop_front();
+        }
+    }
+    if !was_over && model.referee.outcome().is_some() {
+        eprintln!("{}", outcome_text(&model.referee));
+    }
+}
+
+fn outcome_text(referee: &Referee) -> String {
+    match referee.outcome() {
+        Some(Outcome::Finished { winner }) => format!("{} finished in {} turns", referee.bot_name(*winner), referee.turn()),
+        Some(Outcome::LastStanding { winner }) => format!("{} wins, the others lost", referee.bot_name(*winner)),
+        Some(Outcome::NoWinner) => {
+            let reasons: Vec<String> = (0..referee.players())
+                .map(|p| format!("{}: {}", referee.bot_name(p), referee.loss(p).unwrap_or("")))
+                .collect();
+            format!("No winner ({})", reasons.join(", "))
+        }
+        None => String::new(),
+    }
+}
+
+/// Colour of the `i`-th racer, the referee listing the pods team by team.
+fn pod_color(i: usize) -> Rgb {
+    let (r, g, b) = POD_COLORS[i / PODS_PER_TEAM][i % PODS_PER_TEAM];
+    rgb(r, g, b)
+}
+
+fn draw_pod(draw: &Draw, pod: &physics::Pod, color: Rgb) {
+    let pos = from_cg_coords(pod.position.x as f32, pod.position.y as f32);
+    let d = 2.0 * POD_RADIUS as f32 / SCL as f32;
+    draw.ellipse().xy(pos).w_h(d, d).color(color);
+}
+
+fn draw_trail(draw: &Draw, trail: &VecDeque<Point2>, color: Rgb) {
+    let points = trail.iter().map(|pt| from_cg_coords(pt.x, pt.y));
+    draw.polyline().weight(2.0).points(points).color(color);
+}
+
+/// Teams ranked by their best pod's progress, then every pod's lap, next
+/// checkpoint, speed and heading.
+fn draw_scoreboard(draw: &Draw, referee: &Referee) {
+    let track = referee.track();
+    let racers = referee.racers();
+    let mut teams: Vec<usize> = (0..referee.players()).collect();
+    let progress = |team: usize| racers.iter().filter(|r| r.player == team).map(|r| r.passed).max().unwrap_or(0);
+    teams.sort_by_key(|&team| std::cmp::Reverse(progress(team)));
+
+    let mut lines: Vec<(String, Rgb)> = Vec::new();
+    for (rank, &team) in teams.iter().enumerate() {
+        let status = match referee.loss(team) {
+            Some(reason) => format!("out: {}", reason),
+            None => format!("timeout {}", referee.timeout(team)),
+        };
+        lines.push((format!("{}. Team {} ({})  {}", rank + 1, team + 1, referee.bot_name(team), status), rgb(1.0, 1.0, 1.0)));
+        for (i, racer) in racers.iter().enumerate().filter(|(_, r)| r.player == team) {
+            let heading = if racer.pod.angle < 0.0 { "-".to_string() } else { format!("{:.0}", racer.pod.angle) };
+            let text = format!(
+                "    Pod {}  lap {}/{}  next {}  speed {:.0}  heading {}",
+                i % PODS_PER_TEAM + 1,
+                racer.lap(track),
+                track.laps,
+                racer.next_checkpoint,
+                racer.pod.velocity.length(),
+                heading
+            );
+            lines.push((text, pod_color(i)));
+        }
+    }
+
+    let line_h = 15.0;
+    let h = line_h * lines.len() as f32 + 10.0;
+    let left = -(W_SCL as f32) * 0.5;
+    let top = H_SCL as f32 * 0.5;
+    draw.rect().x_y(left + 180.0, top - h * 0.5).w_h(360.0, h).color(srgba(0.0, 0.0, 0.0, 0.6));
+    for (i, (text, color)) in lines.iter().enumerate() {
+        let y = top - 5.0 - line_h * (i as f32 + 0.5);
+        draw.text(text).x_y(left + 180.0, y).w(350.0).h(line_h).left_justify().color(*color);
+    }
 }
 
 fn view(app: &App, model: &Model, frame: Frame){
     let draw = app.draw();
     draw.background().color(BLACK);
     draw.rect().w_h(W_SCL as f32, H_SCL as f32).x_y(0.0, 0.0).color(ORANGE);
-    for (idx, pt) in model.track.iter().enumerate() {
-        draw_circle(&draw, &pt, CIRCLE);
+    for (idx, c) in model.referee.track().checkpoints.iter().enumerate() {
+        let pt = pt2(c.x as f32, c.y as f32);
+        draw_circle(&draw, &pt, CHECKPOINT_RADIUS);
         draw.text(&format!("{}", idx)).xy(from_cg_coords(pt.x, pt.y)).color(BLACK);
     }
-    model.pod.draw(&draw);
+    for (i, (racer, trail)) in model.referee.racers().iter().zip(model.trails.iter()).enumerate() {
+        let color = pod_color(i);
+        draw_trail(&draw, trail, color);
+        draw_pod(&draw, &racer.pod, color);
+    }
+    draw_scoreboard(&draw, &model.referee);
+    if model.referee.outcome().is_some() {
+        draw.text(&outcome_text(&model.referee)).w(W_SCL as f32 - 20.0).color(BLACK);
+    }
     draw.to_frame(app, &frame).unwrap();
-}
\ No newline at end of file
+}
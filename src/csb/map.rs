@@ -0,0 +1,230 @@
+use crate::csb::physics::{Vector, CHECKPOINT_RADIUS, HEIGHT, WIDTH};
+use crate::rng::Pcg32;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+pub const MIN_CHECKPOINTS: usize = 3;
+pub const MAX_CHECKPOINTS: usize = 8;
+pub const DEFAULT_LAPS: usize = 3;
+/// Smallest distance between the centres of two generated checkpoints.
+pub const MIN_SPACING: f64 = 4.0 * CHECKPOINT_RADIUS;
+/// Generated checkpoints keep this far from the edges of the field.
+const MARGIN: f64 = CHECKPOINT_RADIUS;
+
+/// Tracks of the game's own referee.
+pub const OFFICIAL_MAPS: &[&[(i32, i32)]] = &[
+    &[(12460, 1350), (10540, 5980), (3580, 5180), (13580, 7600)],
+    &[(3600, 5280), (13840, 5080), (10680, 2280), (8700, 7460), (7200, 2160)],
+    &[(4560, 2180), (7350, 4940), (3320, 7230), (14580, 7700), (10560, 5060), (13100, 2320)],
+    &[(5010, 5260), (11480, 6080), (9100, 1840)],
+    &[(14660, 1410), (3450, 7220), (9420, 7240), (5970, 4240)],
+    &[(3640, 4420), (8000, 7900), (13300, 5540), (9560, 1400)],
+    &[(4100, 7420), (13500, 2340), (12940, 7220), (5640, 2580)],
+    &[(14520, 7780), (6320, 4290), (7800, 860), (7660, 5970), (3140, 7540), (9520, 4380)],
+    &[(10040, 5970), (13920, 1940), (8020, 3260), (2670, 7020)],
+    &[(7500, 6940), (6000, 5360), (11300, 2820)],
+    &[(4060, 4660), (13040, 1900), (6560, 7840), (7480, 1360), (12700, 7100)],
+    &[(3020, 5190), (6280, 7760), (14100, 7760), (13880, 1220), (10240, 4920), (6100, 2200)],
+    &[(10323, 3366), (11203, 5425), (7259, 6656), (5425, 2838)],
+];
+
+/// Checkpoints of a race and how many laps it lasts. The text format is
+/// the laps on the first line and then one `x y` line per checkpoint, `#`
+/// starting comments.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Track {
+    pub laps: usize,
+    pub checkpoints: Vec<Vector>,
+}
+
+impl Track {
+    pub fn new(laps: usize, checkpoints: Vec<Vector>) -> Result<Track, String> {
+        let track = Track { laps, checkpoints };
+        track.validate()?;
+        Ok(track)
+    }
+
+    pub fn official(index: usize) -> Track {
+        let checkpoints = OFFICIAL_MAPS[index]
+            .iter()
+            .map(|&(x, y)| Vector::new(x as f64, y as f64))
+            .collect();
+        Track {
+            laps: DEFAULT_LAPS,
+            checkpoints,
+        }
+    }
+
+    /// Random track with 3 to 8 checkpoints at least `MIN_SPACING` apart,
+    /// the same for the same seed.
+    pub fn random(seed: u64) -> Track {
+        let mut rng = Pcg32::new(seed);
+        let count = MIN_CHECKPOINTS + rng.below((MAX_CHECKPOINTS - MIN_CHECKPOINTS + 1) as u32) as usize;
+        // Eight checkpoints fit easily, restarting only guards against an
+        // unlucky layout leaving no room
+        loop {
+            let mut checkpoints: Vec<Vector> = Vec::with_capacity(count);
+            for _ in 0..100 * count {
+                if checkpoints.len() == count {
+                    break;
+                }
+                let x = rng.range_f32(MARGIN as f32, (WIDTH - MARGIN) as f32).round() as f64;
+                let y = rng.range_f32(MARGIN as f32, (HEIGHT - MARGIN) as f32).round() as f64;
+                let c = Vector::new(x, y);
+                if checkpoints.iter().all(|&other| other.distance(c) >= MIN_SPACING) {
+                    checkpoints.push(c);
+                }
+            }
+            if checkpoints.len() == count {
+                return Track {
+                    laps: DEFAULT_LAPS,
+                    checkpoints,
+                };
+            }
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let count = self.checkpoints.len();
+        if !(MIN_CHECKPOINTS..=MAX_CHECKPOINTS).contains(&count) {
+            return Err(format!(
+                "track needs {} to {} checkpoints, got {}",
+                MIN_CHECKPOINTS, MAX_CHECKPOINTS, count
+            ));
+        }
+        if self.laps == 0 {
+            return Err("track needs at least one lap".to_string());
+        }
+        for (i, c) in self.checkpoints.iter().enumerate() {
+            if c.x < 0.0 || c.y < 0.0 || c.x > WIDTH || c.y > HEIGHT {
+                return Err(format!("checkpoint {} {} is outside the field", c.x, c.y));
+            }
+            for other in self.checkpoints[i + 1..].iter() {
+                if c.distance(*other) < 2.0 * CHECKPOINT_RADIUS {
+                    return Err(format!(
+                        "checkpoints {} {} and {} {} overlap",
+                        c.x, c.y, other.x, other.y
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Track, String> {
+        fs::read_to_string(path)
+            .map_err(|e| e.to_string())?
+            .parse::<Track>()
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+}
+
+impl FromStr for Track {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Track, String> {
+        let mut lines = s
+            .lines()
+            .map(|line| line.split('#').next().unwrap().trim())
+            .filter(|line| !line.is_empty());
+        let laps = lines
+            .next()
+            .ok_or("empty track")?
+            .parse::<usize>()
+            .map_err(|e| format!("invalid laps: {}", e))?;
+        let checkpoints = lines
+            .map(|line| {
+                let numbers = line
+                    .split_whitespace()
+                    .map(|n| n.parse::<i32>())
+                    .collect::<Result<Vec<i32>, _>>()
+                    .map_err(|e| format!("invalid checkpoint {:?}: {}", line, e))?;
+                match numbers[..] {
+                    [x, y] => Ok(Vector::new(x as f64, y as f64)),
+                    _ => Err(format!("checkpoint {:?} needs x and y", line)),
+                }
+            })
+            .collect::<Result<Vec<Vector>, String>>()?;
+        Track::new(laps, checkpoints)
+    }
+}
+
+impl fmt::Display for Track {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.laps)?;
+        for c in self.checkpoints.iter() {
+            writeln!(f, "{} {}", c.x as i32, c.y as i32)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn official_and_random_tracks_are_valid() {
+        for index in 0..OFFICIAL_MAPS.len() {
+            assert_eq!(Track::official(index).validate(), Ok(()));
+        }
+        for seed in 0..100 {
+            assert_eq!(Track::random(seed).validate(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn overlapping_checkpoints_are_rejected() {
+        let mut track = Track::official(0);
+        let first = track.checkpoints[0];
+        track.checkpoints.push(first + Vector::new(2.0 * CHECKPOINT_RADIUS - 1.0, 0.0));
+        assert!(track.validate().is_err());
+    }
+
+    #[test]
+    fn text_round_trips() {
+        for index in 0..OFFICIAL_MAPS.len() {
+            let track = Track::official(index);
+            assert_eq!(track.to_string().parse::<Track>(), Ok(track));
+        }
+        let track = Track::random(7);
+        assert_eq!(track.to_string().parse::<Track>(), Ok(track));
+    }
+
+    #[test]
+    fn comments_and_blank_lines_are_skipped() {
+        let text = "# laps\n2\n\n1000 1000 # start\n5000 1000\n5000 5000\n";
+        let track = text.parse::<Track>().unwrap();
+        assert_eq!(track.laps, 2);
+        assert_eq!(track.checkpoints[2], Vector::new(5000.0, 5000.0));
+    }
+
+    #[test]
+    fn malformed_text_is_rejected() {
+        let cases = [
+            ("", "empty track"),
+            ("x\n1000 1000\n", "invalid laps"),
+            ("3\n1000 1000\n5000 one\n", "invalid checkpoint"),
+            ("3\n1000 1000\n5000\n", "needs x and y"),
+            ("3\n1000 1000\n5000 1000 10\n", "needs x and y"),
+            ("3\n1000 1000\n5000 1000\n", "checkpoints, got 2"),
+            ("0\n1000 1000\n5000 1000\n5000 5000\n", "at least one lap"),
+            ("3\n1000 1000\n5000 1000\n20000 5000\n", "outside the field"),
+        ];
+        for &(text, error) in cases.iter() {
+            let result = text.parse::<Track>();
+            assert!(
+                matches!(&result, Err(e) if e.contains(error)),
+                "{:?} gave {:?}",
+                text,
+                result
+            );
+        }
+    }
+}
@@ -0,0 +1,8 @@
+// Coders Strike Back: the game rules, bots and referee shared by the `csb`
+// example and the `csb_train` trainer.
+
+pub mod bot;
+pub mod genome;
+pub mod map;
+pub mod physics;
+pub mod referee;
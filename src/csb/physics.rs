@@ -0,0 +1,387 @@
+// Coders Strike Back rules. Coordinates are game units with `y` growing
+// downwards, angles are degrees clockwise from the `x` axis.
+
+use std::f64::consts::PI;
+use std::ops::{Add, AddAssign, Mul, Sub};
+use std::str::FromStr;
+
+pub const WIDTH: f64 = 16000.0;
+pub const HEIGHT: f64 = 9000.0;
+pub const POD_RADIUS: f64 = 400.0;
+pub const CHECKPOINT_RADIUS: f64 = 600.0;
+pub const MAX_THRUST: i32 = 100;
+pub const BOOST_THRUST: f64 = 650.0;
+pub const MAX_ROTATION: f64 = 18.0;
+pub const FRICTION: f64 = 0.85;
+pub const MIN_IMPULSE: f64 = 120.0;
+pub const SHIELD_MASS: f64 = 10.0;
+pub const SHIELD_COOLDOWN: u32 = 3;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Vector {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Vector {
+    pub fn new(x: f64, y: f64) -> Self {
+        Vector { x, y }
+    }
+
+    pub fn dot(self, other: Vector) -> f64 {
+        self.x * other.x + self.y * other.y
+    }
+
+    pub fn length_squared(self) -> f64 {
+        self.dot(self)
+    }
+
+    pub fn length(self) -> f64 {
+        self.length_squared().sqrt()
+    }
+
+    pub fn distance(self, other: Vector) -> f64 {
+        (self - other).length()
+    }
+}
+
+impl Add for Vector {
+    type Output = Vector;
+
+    fn add(self, other: Vector) -> Vector {
+        Vector::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl AddAssign for Vector {
+    fn add_assign(&mut self, other: Vector) {
+        self.x += other.x;
+        self.y += other.y;
+    }
+}
+
+impl Sub for Vector {
+    type Output = Vector;
+
+    fn sub(self, other: Vector) -> Vector {
+        Vector::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl Mul<f64> for Vector {
+    type Output = Vector;
+
+    fn mul(self, k: f64) -> Vector {
+        Vector::new(self.x * k, self.y * k)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Thrust {
+    /// Clamped to `0..=MAX_THRUST`.
+    Power(i32),
+    /// `BOOST_THRUST` once per race, a full thrust afterwards.
+    Boost,
+    /// Heavier for this turn, no thrust for the next `SHIELD_COOLDOWN` turns.
+    Shield,
+}
+
+impl FromStr for Thrust {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Thrust, String> {
+        match s.trim() {
+            "BOOST" => Ok(Thrust::Boost),
+            "SHIELD" => Ok(Thrust::Shield),
+            other => other
+                .parse::<i32>()
+                .map(Thrust::Power)
+                .map_err(|_| format!("invalid thrust {:?}", other)),
+        }
+    }
+}
+
+/// What a pod does in a turn: face `target` as far as it may and thrust.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Action {
+    pub target: Vector,
+    pub thrust: Thrust,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pod {
+    pub position: Vector,
+    pub velocity: Vector,
+    /// Negative before the first turn, in which the pod may face anywhere.
+    pub angle: f64,
+    pub boost_used: bool,
+    pub shield: bool,
+    pub cooldown: u32,
+}
+
+impl Pod {
+    pub fn new(position: Vector) -> Self {
+        Pod {
+            position,
+            velocity: Vector::default(),
+            angle: -1.0,
+            boost_used: false,
+            shield: false,
+            cooldown: 0,
+        }
+    }
+
+    pub fn mass(&self) -> f64 {
+        if self.shield {
+            SHIELD_MASS
+        } else {
+            1.0
+        }
+    }
+
+    /// Absolute angle from the pod to `target`, in `[0, 360)`.
+    pub fn angle_to(&self, target: Vector) -> f64 {
+        let d = target - self.position;
+        let length = d.length();
+        if length == 0.0 {
+            return self.angle.max(0.0);
+        }
+        let a = (d.x / length).max(-1.0).min(1.0).acos() * 180.0 / PI;
+        if d.y < 0.0 {
+            360.0 - a
+        } else {
+            a
+        }
+    }
+
+    /// Signed rotation towards `target`, positive clockwise, in `(-180, 180]`.
+    pub fn diff_angle(&self, target: Vector) -> f64 {
+        let a = self.angle_to(target);
+        let right = if self.angle <= a { a - self.angle } else { 360.0 - self.angle + a };
+        let left = if self.angle >= a { self.angle - a } else { self.angle + 360.0 - a };
+        if right < left {
+            right
+        } else {
+            -left
+        }
+    }
+
+    pub fn rotate(&mut self, target: Vector) {
+        if self.angle < 0.0 {
+            self.angle = self.angle_to(target);
+            return;
+        }
+        let d = self.diff_angle(target).max(-MAX_ROTATION).min(MAX_ROTATION);
+        self.angle = (self.angle + d).rem_euclid(360.0);
+    }
+
+    pub fn thrust(&mut self, thrust: Thrust) {
+        let power = match thrust {
+            Thrust::Shield => {
+                self.shield = true;
+                self.cooldown = SHIELD_COOLDOWN;
+                return;
+            }
+            _ if self.cooldown > 0 => return,
+            Thrust::Boost if !self.boost_used => {
+                self.boost_used = true;
+                BOOST_THRUST
+            }
+            Thrust::Boost => MAX_THRUST as f64,
+            Thrust::Power(power) => power.max(0).min(MAX_THRUST) as f64,
+        };
+        let a = self.angle * PI / 180.0;
+        self.velocity += Vector::new(a.cos(), a.sin()) * power;
+    }
+
+    /// Friction, rounding of the position and truncation of the velocity.
+    pub fn end_turn(&mut self) {
+        self.position = Vector::new(self.position.x.round(), self.position.y.round());
+        self.velocity = Vector::new((self.velocity.x * FRICTION).trunc(), (self.velocity.y * FRICTION).trunc());
+        self.angle = self.angle.round().rem_euclid(360.0);
+        if self.shield {
+            self.shield = false;
+        } else if self.cooldown > 0 {
+            self.cooldown -= 1;
+        }
+    }
+}
+
+/// Time within `[0, limit]` at which two pods moving linearly start touching.
+/// Pods that already overlap collide at once if they are closing in.
+fn collision_time(a: &Pod, b: &Pod, limit: f64) -> Option<f64> {
+    let d = a.position - b.position;
+    let dv = a.velocity - b.velocity;
+    let r2 = (2.0 * POD_RADIUS) * (2.0 * POD_RADIUS);
+    let qa = dv.length_squared();
+    let qb = 2.0 * d.dot(dv);
+    let qc = d.length_squared() - r2;
+    if qb >= 0.0 || qa == 0.0 {
+        return None;
+    }
+    if qc <= 0.0 {
+        return Some(0.0);
+    }
+    let delta = qb * qb - 4.0 * qa * qc;
+    if delta < 0.0 {
+        return None;
+    }
+    let t = (-qb - delta.sqrt()) / (2.0 * qa);
+    if t <= limit {
+        Some(t)
+    } else {
+        None
+    }
+}
+
+/// Elastic bounce of two touching pods, the impulse being at least
+/// `MIN_IMPULSE` as in the game.
+fn bounce(a: &mut Pod, b: &mut Pod) {
+    let (m1, m2) = (a.mass(), b.mass());
+    let mcoeff = (m1 + m2) / (m1 * m2);
+    let n = a.position - b.position;
+    let dv = a.velocity - b.velocity;
+    let product = n.dot(dv);
+    let mut f = n * (product / (n.length_squared() * mcoeff));
+
+    a.velocity = a.velocity - f * (1.0 / m1);
+    b.velocity += f * (1.0 / m2);
+
+    let impulse = f.length();
+    if impulse > 0.0 && impulse < MIN_IMPULSE {
+        f = f * (MIN_IMPULSE / impulse);
+    }
+    a.velocity = a.velocity - f * (1.0 / m1);
+    b.velocity += f * (1.0 / m2);
+}
+
+/// Moves all pods through one turn, resolving the collisions in the order
+/// they happen. Returns the path of each pod, its positions at the start,
+/// at every collision and at the end, between which it went straight.
+pub fn move_pods(pods: &mut [Pod]) -> Vec<Vec<Vector>> {
+    let mut paths: Vec<Vec<Vector>> = pods.iter().map(|pod| vec![pod.position]).collect();
+    let mut t = 0.0;
+    // Guards against pods pinned against each other colliding forever
+    let mut remaining_collisions = 16 * pods.len().max(1);
+    while t < 1.0 {
+        let mut first: Option<(f64, usize, usize)> = None;
+        if remaining_collisions > 0 {
+            for i in 0..pods.len() {
+                for j in i + 1..pods.len() {
+                    let limit = first.map_or(1.0 - t, |(ct, _, _)| ct);
+                    if let Some(ct) = collision_time(&pods[i], &pods[j], limit) {
+                        first = Some((ct, i, j));
+                    }
+                }
+            }
+        }
+        let dt = first.map_or(1.0 - t, |(ct, _, _)| ct);
+        for (pod, path) in pods.iter_mut().zip(paths.iter_mut()) {
+            pod.position += pod.velocity * dt;
+            path.push(pod.position);
+        }
+        t += dt;
+        match first {
+            Some((_, i, j)) => {
+                let (left, right) = pods.split_at_mut(j);
+                bounce(&mut left[i], &mut right[0]);
+                remaining_collisions -= 1;
+            }
+            None => break,
+        }
+    }
+    paths
+}
+
+/// Plays one turn: every pod rotates and thrusts, then all move together,
+/// bounce off each other and slow down. Returns the paths of `move_pods`.
+pub fn play_turn(pods: &mut [Pod], actions: &[Action]) -> Vec<Vec<Vector>> {
+    assert_eq!(pods.len(), actions.len());
+    for (pod, action) in pods.iter_mut().zip(actions.iter()) {
+        pod.rotate(action.target);
+        pod.thrust(action.thrust);
+    }
+    let paths = move_pods(pods);
+    for pod in pods.iter_mut() {
+        pod.end_turn();
+    }
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: Vector, b: Vector) {
+        assert!(a.distance(b) < 1e-6, "{:?} is not {:?}", a, b);
+    }
+
+    #[test]
+    fn thrusting_pod_turns_at_most_18_degrees_and_is_rounded() {
+        let mut pods = [Pod::new(Vector::new(1000.0, 1000.0))];
+        pods[0].angle = 0.0;
+        let action = Action {
+            target: Vector::new(1000.0, 8000.0),
+            thrust: Thrust::Power(100),
+        };
+        let expected = [
+            (1095.0, 1031.0, 80.0, 26.0, 18.0),
+            (1256.0, 1116.0, 136.0, 72.0, 36.0),
+            (1451.0, 1269.0, 165.0, 129.0, 54.0),
+            (1647.0, 1493.0, 166.0, 190.0, 72.0),
+            (1813.0, 1783.0, 141.0, 246.0, 90.0),
+        ];
+        for &(x, y, vx, vy, angle) in expected.iter() {
+            play_turn(&mut pods, &[action]);
+            assert_eq!(pods[0].position, Vector::new(x, y));
+            assert_eq!(pods[0].velocity, Vector::new(vx, vy));
+            assert_eq!(pods[0].angle, angle);
+        }
+    }
+
+    fn head_on(a_shield: bool) -> [Pod; 2] {
+        let mut a = Pod::new(Vector::new(1000.0, 1000.0));
+        a.velocity = Vector::new(100.0, 0.0);
+        a.shield = a_shield;
+        let mut b = Pod::new(Vector::new(1900.0, 1000.0));
+        b.velocity = Vector::new(-100.0, 0.0);
+        let mut pods = [a, b];
+        move_pods(&mut pods);
+        pods
+    }
+
+    #[test]
+    fn slow_collision_gets_the_minimum_impulse() {
+        // The pods touch halfway, where the elastic impulse of 100 would
+        // only stop them
+        let pods = head_on(false);
+        assert_near(pods[0].velocity, Vector::new(-MIN_IMPULSE, 0.0));
+        assert_near(pods[1].velocity, Vector::new(MIN_IMPULSE, 0.0));
+        assert_near(pods[0].position, Vector::new(990.0, 1000.0));
+        assert_near(pods[1].position, Vector::new(1910.0, 1000.0));
+    }
+
+    #[test]
+    fn shield_makes_the_pod_ten_times_heavier() {
+        let mut pod = Pod::new(Vector::default());
+        pod.angle = 0.0;
+        pod.thrust(Thrust::Shield);
+        assert_eq!(pod.mass(), SHIELD_MASS);
+        assert_eq!(pod.velocity, Vector::default());
+
+        let pods = head_on(true);
+        assert_near(pods[0].velocity, Vector::new(700.0 / 11.0, 0.0));
+        assert_near(pods[1].velocity, Vector::new(2900.0 / 11.0, 0.0));
+
+        // No thrust while cooling down, the mass being back to normal
+        pod.end_turn();
+        assert_eq!(pod.mass(), 1.0);
+        for _ in 0..SHIELD_COOLDOWN {
+            pod.thrust(Thrust::Power(MAX_THRUST));
+            assert_eq!(pod.velocity, Vector::default());
+            pod.end_turn();
+        }
+        pod.thrust(Thrust::Power(MAX_THRUST));
+        assert_eq!(pod.velocity, Vector::new(MAX_THRUST as f64, 0.0));
+    }
+}
@@ -0,0 +1,339 @@
+use crate::csb::bot::{Bot, PodState};
+use crate::csb::map::Track;
+use crate::csb::physics::{self, Action, Vector, CHECKPOINT_RADIUS, POD_RADIUS};
+
+/// Turns a player may go without any of its pods reaching a checkpoint.
+pub const CHECKPOINT_TIMEOUT: u32 = 100;
+/// Gap between the starting pods, measured between their edges.
+const START_GAP: f64 = 300.0;
+
+/// A pod in the race and its progress.
+#[derive(Clone, Copy, Debug)]
+pub struct Racer {
+    pub pod: physics::Pod,
+    pub player: usize,
+    pub next_checkpoint: usize,
+    /// Checkpoints passed since the start, the race being won at
+    /// `laps * checkpoints`.
+    pub passed: usize,
+}
+
+impl Racer {
+    /// Lap the pod is running, counting from 1 and staying at `laps` once
+    /// it finished.
+    pub fn lap(&self, track: &Track) -> usize {
+        (self.passed / track.checkpoints.len() + 1).min(track.laps)
+    }
+
+    pub fn finished(&self, track: &Track) -> bool {
+        self.passed >= track.laps * track.checkpoints.len()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+    /// A pod of the player completed every lap first.
+    Finished { winner: usize },
+    /// The other players lost, by timeout or invalid answers.
+    LastStanding { winner: usize },
+    /// Every player lost, the only outcome left to solo races.
+    NoWinner,
+}
+
+/// Whether a pod going straight from `a` to `b` gets its centre inside the
+/// checkpoint at `c`, so fast pods cannot jump over it between two turns.
+fn passes_through(a: Vector, b: Vector, c: Vector) -> bool {
+    let ab = b - a;
+    let length_squared = ab.length_squared();
+    let t = if length_squared == 0.0 {
+        0.0
+    } else {
+        ((c - a).dot(ab) / length_squared).max(0.0).min(1.0)
+    };
+    (a + ab * t).distance(c) < CHECKPOINT_RADIUS
+}
+
+/// Runs a race between bots, each controlling `pods_per_player` pods.
+pub struct Referee {
+    track: Track,
+    bots: Vec<Box<dyn Bot>>,
+    racers: Vec<Racer>,
+    pods_per_player: usize,
+    /// Turns left before each player is eliminated.
+    timeouts: Vec<u32>,
+    /// Why each player was eliminated, if it was.
+    losses: Vec<Option<String>>,
+    turn: u32,
+    outcome: Option<Outcome>,
+}
+
+impl Referee {
+    /// Lines the pods up across the first checkpoint, perpendicular to the
+    /// way to the second one, and sends the track to every bot.
+    pub fn new(track: Track, bots: Vec<Box<dyn Bot>>, pods_per_player: usize) -> Self {
+        assert!(track.checkpoints.len() >= 2, "a track needs at least two checkpoints");
+        let start = track.checkpoints[0];
+        let d = track.checkpoints[1] - start;
+        let across = Vector::new(-d.y, d.x) * (1.0 / d.length().max(1.0));
+        let total = bots.len() * pods_per_player;
+        let spacing = 2.0 * POD_RADIUS + START_GAP;
+        let racers = (0..total)
+            .map(|i| {
+                let offset = (i as f64 - (total - 1) as f64 / 2.0) * spacing;
+                let p = start + across * offset;
+                Racer {
+                    pod: physics::Pod::new(Vector::new(p.x.round(), p.y.round())),
+                    player: i / pods_per_player,
+                    next_checkpoint: 1,
+                    passed: 0,
+                }
+            })
+            .collect();
+
+        let players = bots.len();
+        let mut referee = Referee {
+            track,
+            bots,
+            racers,
+            pods_per_player,
+            timeouts: vec![CHECKPOINT_TIMEOUT; players],
+            losses: vec![None; players],
+            turn: 0,
+            outcome: None,
+        };
+        for player in 0..players {
+            let result = referee.bots[player].init(referee.track.laps, &referee.track.checkpoints);
+            if let Err(error) = result {
+                referee.eliminate(player, error);
+            }
+        }
+        referee.check_outcome();
+        referee
+    }
+
+    pub fn track(&self) -> &Track {
+        &self.track
+    }
+
+    pub fn racers(&self) -> &[Racer] {
+        &self.racers
+    }
+
+    pub fn players(&self) -> usize {
+        self.bots.len()
+    }
+
+    pub fn pods_per_player(&self) -> usize {
+        self.pods_per_player
+    }
+
+    pub fn bot_name(&self, player: usize) -> String {
+        self.bots[player].name()
+    }
+
+    pub fn timeout(&self, player: usize) -> u32 {
+        self.timeouts[player]
+    }
+
+    pub fn loss(&self, player: usize) -> Option<&str> {
+        self.losses[player].as_deref()
+    }
+
+    pub fn turn(&self) -> u32 {
+        self.turn
+    }
+
+    pub fn outcome(&self) -> Option<&Outcome> {
+        self.outcome.as_ref()
+    }
+
+    fn eliminate(&mut self, player: usize, reason: String) {
+        if self.losses[player].is_none() {
+            self.losses[player] = Some(reason);
+        }
+    }
+
+    fn states(&self, player: usize) -> (Vec<PodState>, Vec<PodState>) {
+        let state = |r: &Racer| PodState::new(&r.pod, r.next_checkpoint, r.passed);
+        let own = self.racers.iter().filter(|r| r.player == player).map(state).collect();
+        let opponents = self.racers.iter().filter(|r| r.player != player).map(state).collect();
+        (own, opponents)
+    }
+
+    /// Asks every bot for its actions and plays them. Pods of eliminated
+    /// players drift without thrust. Does nothing once the race is over.
+    pub fn step(&mut self) {
+        if self.outcome.is_some() {
+            return;
+        }
+        let mut actions = Vec::with_capacity(self.racers.len());
+        for player in 0..self.players() {
+            let (own, opponents) = self.states(player);
+            let idle: Vec<Action> = own
+                .iter()
+                .map(|pod| Action {
+                    target: self.track.checkpoints[pod.next_checkpoint],
+                    thrust: physics::Thrust::Power(0),
+                })
+                .collect();
+            if self.losses[player].is_some() {
+                actions.extend(idle);
+                continue;
+            }
+            match self.bots[player].play(&own, &opponents) {
+                Ok(answer) if answer.len() == own.len() => actions.extend(answer),
+                Ok(answer) => {
+                    let reason = format!("answered {} actions for {} pods", answer.len(), own.len());
+                    actions.extend(idle);
+                    self.eliminate(player, reason);
+                }
+                Err(error) => {
+                    actions.extend(idle);
+                    self.eliminate(player, error);
+                }
+            }
+        }
+
+        let mut pods: Vec<physics::Pod> = self.racers.iter().map(|r| r.pod).collect();
+        let paths = physics::play_turn(&mut pods, &actions);
+        self.turn += 1;
+
+        let mut finished = None;
+        for t in self.timeouts.iter_mut() {
+            *t = t.saturating_sub(1);
+        }
+        for ((racer, pod), path) in self.racers.iter_mut().zip(pods).zip(paths) {
+            racer.pod = pod;
+            let checkpoint = self.track.checkpoints[racer.next_checkpoint];
+            // The pod went straight between collisions, each leg of its path
+            // may cross the checkpoint
+            if path.windows(2).any(|leg| passes_through(leg[0], leg[1], checkpoint)) {
+                racer.passed += 1;
+                racer.next_checkpoint = (racer.next_checkpoint + 1) % self.track.checkpoints.len();
+                self.timeouts[racer.player] = CHECKPOINT_TIMEOUT;
+                // Pods of eliminated players still drift but cannot win
+                let alive = self.losses[racer.player].is_none();
+                if racer.finished(&self.track) && alive && finished.is_none() {
+                    finished = Some(racer.player);
+                }
+            }
+        }
+        for player in 0..self.players() {
+            if self.timeouts[player] == 0 {
+                self.eliminate(player, format!("no checkpoint in {} turns", CHECKPOINT_TIMEOUT));
+            }
+        }
+
+        match finished {
+            Some(winner) => self.outcome = Some(Outcome::Finished { winner }),
+            None => self.check_outcome(),
+        }
+    }
+
+    /// Ends the race once at most one player is left in a multi-player race,
+    /// or nobody is in a solo one.
+    fn check_outcome(&mut self) {
+        let alive: Vec<usize> = (0..self.players()).filter(|&p| self.losses[p].is_none()).collect();
+        self.outcome = match alive.len() {
+            0 => Some(Outcome::NoWinner),
+            1 if self.players() > 1 => Some(Outcome::LastStanding { winner: alive[0] }),
+            _ => None,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csb::physics::Thrust;
+
+    /// Coasts, so that only collisions change the pods' paths.
+    struct Idle;
+
+    impl Bot for Idle {
+        fn name(&self) -> String {
+            "idle".to_string()
+        }
+
+        fn play(&mut self, own: &[PodState], _opponents: &[PodState]) -> Result<Vec<Action>, String> {
+            Ok(own
+                .iter()
+                .map(|pod| Action {
+                    target: pod.position(),
+                    thrust: Thrust::Power(0),
+                })
+                .collect())
+        }
+    }
+
+    #[test]
+    fn fast_pods_pass_checkpoints_between_turns() {
+        let c = Vector::new(5000.0, 5000.0);
+        assert!(passes_through(Vector::new(3000.0, 5000.0), Vector::new(7000.0, 5000.0), c));
+        assert!(passes_through(Vector::new(3000.0, 5500.0), Vector::new(7000.0, 5500.0), c));
+        assert!(!passes_through(Vector::new(3000.0, 5700.0), Vector::new(7000.0, 5700.0), c));
+        assert!(!passes_through(Vector::new(3000.0, 5000.0), Vector::new(4000.0, 5000.0), c));
+    }
+
+    #[test]
+    fn pods_bouncing_back_still_pass_the_checkpoint_they_touched() {
+        let checkpoint = Vector::new(5900.0, 5000.0);
+        let track = Track {
+            laps: 1,
+            checkpoints: vec![Vector::new(1000.0, 1000.0), checkpoint, Vector::new(12000.0, 8000.0)],
+        };
+        let mut referee = Referee::new(track, vec![Box::new(Idle), Box::new(Idle)], 1);
+        // Head on, they touch at 5600 and 6400 and bounce back to 5200 and
+        // 6800, both ends of either pod being over 600 from the checkpoint
+        let starts = [Vector::new(5000.0, 5000.0), Vector::new(7000.0, 5000.0)];
+        for (racer, &start) in referee.racers.iter_mut().zip(starts.iter()) {
+            racer.pod.position = start;
+            racer.pod.velocity = Vector::new(6000.0 - start.x, 0.0);
+        }
+        referee.step();
+        for (racer, &start) in referee.racers().iter().zip(starts.iter()) {
+            assert!(!passes_through(start, racer.pod.position, checkpoint));
+            assert_eq!(racer.passed, 1);
+            assert_eq!(racer.next_checkpoint, 2);
+        }
+    }
+
+    #[test]
+    fn every_pod_of_a_two_by_two_race_keeps_its_own_progress() {
+        let track = Track {
+            laps: 1,
+            checkpoints: vec![
+                Vector::new(1000.0, 1000.0),
+                Vector::new(5900.0, 5000.0),
+                Vector::new(12000.0, 8000.0),
+            ],
+        };
+        let mut referee = Referee::new(track, vec![Box::new(Idle), Box::new(Idle)], 2);
+        let players: Vec<usize> = referee.racers().iter().map(|r| r.player).collect();
+        assert_eq!(players, vec![0, 0, 1, 1]);
+
+        // The first pods of both teams collide over checkpoint 1, the second
+        // pod of the first team crosses the last checkpoint and the other
+        // one waits away from everything
+        let setup = [
+            (Vector::new(5000.0, 5000.0), 1000.0, 1, 0),
+            (Vector::new(11000.0, 8000.0), 1500.0, 2, 2),
+            (Vector::new(7000.0, 5000.0), -1000.0, 1, 0),
+            (Vector::new(2000.0, 8000.0), 0.0, 1, 0),
+        ];
+        for (racer, &(position, vx, next_checkpoint, passed)) in referee.racers.iter_mut().zip(setup.iter()) {
+            racer.pod.position = position;
+            racer.pod.velocity = Vector::new(vx, 0.0);
+            racer.next_checkpoint = next_checkpoint;
+            racer.passed = passed;
+        }
+        referee.step();
+
+        let progress: Vec<(usize, usize)> =
+            referee.racers().iter().map(|r| (r.passed, r.next_checkpoint)).collect();
+        assert_eq!(progress, vec![(1, 2), (3, 0), (1, 2), (0, 1)]);
+        let finished: Vec<bool> = referee.racers().iter().map(|r| r.finished(referee.track())).collect();
+        assert_eq!(finished, vec![false, true, false, false]);
+        assert_eq!(referee.outcome(), Some(&Outcome::Finished { winner: 0 }));
+    }
+}
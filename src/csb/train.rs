@@ -0,0 +1,136 @@
+use doom_fire::args::{exit_with_usage, Args};
+use doom_fire::csb::genome::{fitness, tournament, Genome};
+use doom_fire::csb::map::{Track, OFFICIAL_MAPS};
+use doom_fire::rng::Pcg32;
+use rayon::prelude::*;
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const USAGE: &str = "Usage: csb_train [options]
+
+Evolves pod steering networks over solo races without opening a window and
+writes the best one as JSON, which `csb --genome <file>` replays.
+
+Options:
+  --generations <n>    generations to run (default 100)
+  --population <n>     genomes per generation (default 64)
+  --tracks <n>         random tracks raced each generation besides the
+                       official ones (default 4)
+  --turns <n>          turns a race may last (default 600)
+  --seed <n>           seed of the whole run (default from the clock)
+  --from <file.json>   start from a saved genome instead of random ones
+  --out <file.json>    best genome, rewritten every generation (default
+                       genome.json)";
+
+/// Share of each generation copied unchanged to the next.
+const ELITE: f64 = 0.1;
+const MUTATION_RATE: f32 = 0.1;
+const MUTATION_AMOUNT: f64 = 0.5;
+
+struct Options {
+    generations: usize,
+    population: usize,
+    tracks: usize,
+    turns: u32,
+    seed: u64,
+    from: Option<String>,
+    out: String,
+}
+
+/// Nanoseconds since the epoch, printed so that the run can be replayed.
+fn clock_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos() as u64)
+}
+
+/// Reads the options after the program name, `--help` having been handled.
+fn parse_options<I: Iterator<Item = String>>(mut args: Args<I>) -> Result<Options, String> {
+    let mut options = Options {
+        generations: 100,
+        population: 64,
+        tracks: 4,
+        turns: 600,
+        seed: clock_seed(),
+        from: None,
+        out: "genome.json".to_string(),
+    };
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--generations" => options.generations = args.parse(&flag)?,
+            "--population" => options.population = args.parse(&flag)?,
+            "--tracks" => options.tracks = args.parse(&flag)?,
+            "--turns" => options.turns = args.parse(&flag)?,
+            "--seed" => options.seed = args.parse(&flag)?,
+            "--from" => options.from = Some(args.value(&flag)?),
+            "--out" => options.out = args.value(&flag)?,
+            other => return Err(format!("Unknown option {}", other)),
+        }
+    }
+    if options.population < 2 || options.turns == 0 {
+        return Err("Population needs at least 2 genomes and races at least 1 turn".to_string());
+    }
+    Ok(options)
+}
+
+fn main() {
+    if std::env::args().skip(1).any(|arg| arg == "--help" || arg == "-h") {
+        println!("{}", USAGE);
+        process::exit(0);
+    }
+    let options = parse_options(Args::from_env()).unwrap_or_else(|message| exit_with_usage(&message, USAGE));
+    println!("Seed {}", options.seed);
+    let mut rng = Pcg32::new(options.seed);
+
+    let mut population: Vec<Genome> = match &options.from {
+        Some(path) => {
+            let start = Genome::load(path).unwrap_or_else(|error| {
+                eprintln!("Could not load {}: {}", path, error);
+                process::exit(1);
+            });
+            let mut population = vec![start.clone()];
+            while population.len() < options.population {
+                let mut child = start.clone();
+                child.mutate(&mut rng, MUTATION_RATE, MUTATION_AMOUNT);
+                population.push(child);
+            }
+            population
+        }
+        None => (0..options.population).map(|_| Genome::random(&mut rng)).collect(),
+    };
+
+    let elite = ((options.population as f64 * ELITE).ceil() as usize).max(1);
+    for generation in 0..options.generations {
+        // New random tracks every generation keep the genomes from learning
+        // a single layout
+        let mut tracks: Vec<Track> = (0..OFFICIAL_MAPS.len()).map(Track::official).collect();
+        tracks.extend((0..options.tracks).map(|_| Track::random(rng.next_u32() as u64)));
+
+        let mut scored: Vec<(Genome, f64)> = population
+            .into_par_iter()
+            .map(|genome| {
+                let score = fitness(&genome, &tracks, options.turns);
+                (genome, score)
+            })
+            .collect();
+        scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+
+        let (best, best_score) = &scored[0];
+        let mean = scored.iter().map(|(_, score)| score).sum::<f64>() / scored.len() as f64;
+        println!("Generation {}: best {:.0}, mean {:.0}", generation + 1, best_score, mean);
+        if let Err(error) = best.save(&options.out) {
+            eprintln!("Could not write {}: {}", options.out, error);
+            process::exit(1);
+        }
+
+        population = scored.iter().take(elite).map(|(genome, _)| genome.clone()).collect();
+        while population.len() < options.population {
+            let a = tournament(&scored, &mut rng);
+            let b = tournament(&scored, &mut rng);
+            let mut child = a.crossover(b, &mut rng);
+            child.mutate(&mut rng, MUTATION_RATE, MUTATION_AMOUNT);
+            population.push(child);
+        }
+    }
+    println!("Wrote {}", options.out);
+}
@@ -1,100 +1,177 @@
 use nannou::prelude::*; 
 use nannou::ui::prelude::*; 
+use doom_fire::fire_grid::FireGrid;
+use doom_fire::palette::{sort_stops, Palette};
+use doom_fire::pixels::{PixelBuffer, PixelTexture};
 
 fn main() {
     nannou::app(model)
         .update(update)
-        .simple_window(view)
         .run();
 }
 
-const SIZE: f32 = 360.0;
-const HALF_SIZE: f32 = SIZE * 0.5;
+const SIZE: u32 = 360;
 const SCL: f32 = 10.0;
-const HALF_SCL: f32 = SCL * 0.5;
-const SIZE_SCL: f32 = SIZE / SCL;
-const N: f32 = SIZE_SCL * SIZE_SCL;
-
-fn idx_to_x_y(idx: usize) -> (f32, f32) {
-    let s = SIZE_SCL as usize;
-    let x = (idx % s) as f32 * SCL - HALF_SIZE + HALF_SCL;
-    let y = (idx / s) as f32 * SCL - HALF_SIZE + HALF_SCL;
-    (x, y)
+const MIN_SCL: f32 = 1.0;
+const MAX_SCL: f32 = 40.0;
+
+fn grid_size(w: f32, h: f32, scl: f32) -> (usize, usize) {
+    let cols = (w / scl).ceil().max(1.0) as usize;
+    let rows = (h / scl).ceil().max(2.0) as usize;
+    (cols, rows)
 }
 
 struct Model {
-    particles: Vec<Particle>,
+    window: WindowId,
+    fire: FireGrid,
+    scl: f32,
+    pixels: PixelBuffer,
+    texture: PixelTexture,
     ui: Ui,
     ids: Ids,
-    frequency: f32,
-    r_phase: f32,
-    g_phase: f32,
-    b_phase: f32,
+    palettes: Vec<Palette>,
+    palette_idx: usize,
+    preset_path: String,
 }
 
 widget_ids! {
     struct Ids {
+        palette_label,
         frequency,
         r_phase,
         g_phase,
         b_phase,
-    }
-}
-
-struct Particle {
-    intensity: u32,
-}
-impl Particle {
-    pub fn new(intensity: u32) -> Self {
-        Particle {
-            intensity
-        }
-    }
-    fn decay(&mut self, another: u32) {
-        if another == 0  { return; }
-        self.intensity = another + 1;
+        stops[],
     }
 }
 
 fn model(app: &App) -> Model {
-    app.set_loop_mode(LoopMode::Wait);
+    app.set_loop_mode(LoopMode::refresh_sync());
+
+    let window = app
+        .new_window()
+        .size(SIZE, SIZE)
+        .view(view)
+        .key_pressed(key_pressed)
+        .resized(resized)
+        .build()
+        .unwrap();
 
     let mut ui = app.new_ui().build().unwrap();
 
     let ids = Ids::new(ui.widget_id_generator());
 
-    let mut particles: Vec<Particle> = Vec::new();
-    for i in 0..N as usize {
-        let intensity = if i < SIZE_SCL as usize { 35 } else { 0 };
-        particles.push(Particle::new(intensity))
+    let (cols, rows) = grid_size(SIZE as f32, SIZE as f32, SCL);
+    let fire = FireGrid::new(cols, rows, 35, random::<u64>());
+    let pixels = PixelBuffer::new(cols, rows);
+    let texture = PixelTexture::new(&app.window(window).unwrap(), cols, rows);
+
+    let mut palettes = vec![
+        Palette::cosine(0.57, 7.0, 1.5, 8.5),
+        Palette::doom(),
+        Palette::fire_gradient(),
+    ];
+    let mut palette_idx = 0;
+    if let Some(path) = std::env::args().nth(1) {
+        match Palette::load_lookup(&path) {
+            Ok(palette) => {
+                palette_idx = palettes.len();
+                palettes.push(palette);
+            }
+            Err(error) => eprintln!("Could not load palette {:?}: {}", path, error),
+        }
     }
-    let frequency = 0.57;
-    let r_phase = 7.0;
-    let g_phase = 1.5;
-    let b_phase = 8.5;
+    let preset_path = app.exe_name().unwrap() + "_palette.json";
 
     Model {
-        particles,
+        window,
+        fire,
+        scl: SCL,
+        pixels,
+        texture,
         ui,
         ids,
-        frequency,
-        r_phase,
-        g_phase,
-        b_phase,
+        palettes,
+        palette_idx,
+        preset_path,
+    }
+}
+
+fn resize_fire(model: &mut Model, w: f32, h: f32) {
+    let (cols, rows) = grid_size(w, h, model.scl);
+    model.fire.resize(cols, rows);
+}
+
+fn resized(_app: &App, model: &mut Model, size: Vector2) {
+    resize_fire(model, size.x, size.y);
+}
+
+fn key_pressed(app: &App, model: &mut Model, key: Key) {
+    let fire = &mut model.fire;
+    match key {
+        Key::Space => {
+            if fire.is_lit() {
+                fire.extinguish();
+            } else {
+                fire.ignite();
+            }
+        }
+        Key::Left => {
+            fire.set_wind(fire.wind() - 1);
+        }
+        Key::Right => {
+            fire.set_wind(fire.wind() + 1);
+        }
+        Key::Up => {
+            if fire.decay() > 0 {
+                fire.set_decay(fire.decay() - 1);
+            }
+        }
+        Key::Down => {
+            fire.set_decay(fire.decay() + 1);
+        }
+        Key::Equals => {
+            model.scl = (model.scl + 1.0).min(MAX_SCL);
+            let (w, h) = app.window_rect().w_h();
+            resize_fire(model, w, h);
+        }
+        Key::Minus => {
+            model.scl = (model.scl - 1.0).max(MIN_SCL);
+            let (w, h) = app.window_rect().w_h();
+            resize_fire(model, w, h);
+        }
+        Key::P => {
+            model.palette_idx = (model.palette_idx + 1) % model.palettes.len();
+        }
+        Key::S => {
+            let palette = &model.palettes[model.palette_idx];
+            match palette.save(&model.preset_path) {
+                Ok(()) => println!("Saved palette to {}", model.preset_path),
+                Err(error) => eprintln!("Could not save palette: {}", error),
+            }
+        }
+        Key::L => {
+            match Palette::load(&model.preset_path) {
+                Ok(palette) => model.palettes[model.palette_idx] = palette,
+                Err(error) => eprintln!("Could not load palette: {}", error),
+            }
+        }
+        _other_key => {}
     }
 }
 
-fn intensity_to_color(intensity: u32, model: &Model) -> Rgb {
-    let n = 360.0 * (intensity as f32 / 36.0);
-    let i = deg_to_rad(n);
-    let r = (i * model.frequency + model.r_phase).sin() * 0.5 + 0.5;
-    let g = (i * model.frequency + model.g_phase).sin() * 0.5 + 0.5;
-    let b = (i * model.frequency + model.b_phase).sin() * 0.5 + 0.5;
-    srgb(r, g, b)
+fn intensity_to_rgba(intensity: u32, max_intensity: u32, palette: &Palette) -> [u8; 4] {
+    if intensity == 0 {
+        return [0, 0, 0, 255];
+    }
+    let [r, g, b] = palette.color(intensity as f32 / max_intensity as f32);
+    [r, g, b, 255]
 }
 
-fn update(_app: &App, model: &mut Model, _update: Update) {
+fn update(app: &App, model: &mut Model, _update: Update) {
     let ui = &mut model.ui.set_widgets();
+    let ids = &mut model.ids;
+    let palette = &mut model.palettes[model.palette_idx];
 
     fn slider(val: f32, min: f32, max: f32) -> widget::Slider<'static, f32> {
         widget::Slider::new(val, min, max)
@@ -105,59 +182,93 @@ fn update(_app: &App, model: &mut Model, _update: Update) {
             .border(0.0)
     }
 
-    for value in slider(model.frequency as f32, 0.1, 1.0)
+    widget::Text::new(&format!("{} palette (P to switch)", palette.name()))
         .top_left_with_margins(10.0, 20.0)
-        .label(&format!("Frequency {}", model.frequency))
-        .set(model.ids.frequency, ui)
-    {
-        model.frequency = value as f32;
-    }
-    for value in slider(model.r_phase as f32, 0.0, 10.0)
-        .top_left_with_margins(40.0, 20.0)
-        .label(&format!("R phase: {}", model.r_phase))
-        .set(model.ids.r_phase, ui)
-    {
-        model.r_phase = value as f32;
-    }
-    for value in slider(model.g_phase as f32, 0.0, 10.0)
-        .top_left_with_margins(70.0, 20.0)
-        .label(&format!("G phase: {}", model.g_phase))
-        .set(model.ids.g_phase, ui)
-    {
-        model.g_phase = value as f32;
-    }
-    for value in slider(model.b_phase as f32, 0.0, 10.0)
-        .top_left_with_margins(100.0, 20.0)
-        .label(&format!("B phase: {}", model.b_phase))
-        .set(model.ids.b_phase, ui)
-    {
-        model.b_phase = value as f32;
-    }
+        .w_h(200.0, 20.0)
+        .font_size(15)
+        .rgb(1.0, 1.0, 1.0)
+        .set(ids.palette_label, ui);
 
-    let particles = &mut model.particles;
-
-    for index in 0..N as usize {
-        spread_fire(index, particles);
+    match palette {
+        Palette::Cosine { frequency, r_phase, g_phase, b_phase } => {
+            for value in slider(*frequency, 0.1, 1.0)
+                .top_left_with_margins(40.0, 20.0)
+                .label(&format!("Frequency {}", frequency))
+                .set(ids.frequency, ui)
+            {
+                *frequency = value;
+            }
+            for value in slider(*r_phase, 0.0, 10.0)
+                .top_left_with_margins(70.0, 20.0)
+                .label(&format!("R phase: {}", r_phase))
+                .set(ids.r_phase, ui)
+            {
+                *r_phase = value;
+            }
+            for value in slider(*g_phase, 0.0, 10.0)
+                .top_left_with_margins(100.0, 20.0)
+                .label(&format!("G phase: {}", g_phase))
+                .set(ids.g_phase, ui)
+            {
+                *g_phase = value;
+            }
+            for value in slider(*b_phase, 0.0, 10.0)
+                .top_left_with_margins(130.0, 20.0)
+                .label(&format!("B phase: {}", b_phase))
+                .set(ids.b_phase, ui)
+            {
+                *b_phase = value;
+            }
+        }
+        Palette::Gradient { stops } => {
+            if ids.stops.len() < stops.len() {
+                ids.stops.resize(stops.len(), &mut ui.widget_id_generator());
+            }
+            let mut moved = false;
+            for (i, stop) in stops.iter_mut().enumerate() {
+                for value in slider(stop.position, 0.0, 1.0)
+                    .top_left_with_margins(40.0 + 30.0 * i as f32, 20.0)
+                    .label(&format!("Stop {}: {:.2}", i, stop.position))
+                    .set(ids.stops[i], ui)
+                {
+                    stop.position = value;
+                    moved = true;
+                }
+            }
+            if moved {
+                sort_stops(stops);
+            }
+        }
+        Palette::Lookup { .. } => {}
     }
 
-}
+    model.fire.step();
 
-fn spread_fire(index: usize, particles: &mut Vec<Particle>) {
-    if index < SIZE_SCL as usize { return; }
-    let down_index = index - SIZE_SCL as usize;
-    let down_intensity = particles[down_index].intensity;
-    particles[index].decay(down_intensity);
+    let fire = &model.fire;
+    if model.pixels.width() != fire.width() || model.pixels.height() != fire.height() {
+        model.pixels.resize(fire.width(), fire.height());
+    }
+    if !model.texture.matches(&model.pixels) {
+        let window = app.window(model.window).unwrap();
+        model.texture = PixelTexture::new(&window, fire.width(), fire.height());
+    }
+    let max_intensity = fire.max_intensity();
+    let palette = &model.palettes[model.palette_idx];
+    model.pixels.fill_from(fire.intensities(), |&intensity| {
+        intensity_to_rgba(intensity, max_intensity, palette)
+    });
 }
 
 
 fn view(app: &App, model: &Model, frame: Frame){
     let draw = app.draw();
     draw.background().color(BLACK);
-    for (idx, particle) in model.particles.iter().enumerate() {
-        let (x, y) = idx_to_x_y(idx);
-        //println!("{} {} {}", x, y, particle.intensity);
-        draw.rect().x_y(x, y).w_h(SCL, SCL).color(intensity_to_color(particle.intensity, model));
-    }
+    let rect = app.window_rect();
+    let w = model.pixels.width() as f32 * model.scl;
+    let h = model.pixels.height() as f32 * model.scl;
+    let fire_rect = Rect::from_x_y_w_h(rect.left() + w * 0.5, rect.bottom() + h * 0.5, w, h);
+    model.texture.upload(app, &frame, &model.pixels);
+    model.texture.draw(&draw, fire_rect);
     draw.to_frame(app, &frame).unwrap();
     model.ui.draw_to_frame(app, &frame).unwrap();
-}
\ No newline at end of file
+}
@@ -0,0 +1,183 @@
+use crate::rng::Pcg32;
+
+const DEFAULT_DECAY: u32 = 3;
+
+/// Intensity grid behind the Doom fire example. Row 0 is the source row at
+/// the bottom of the screen, indices grow left to right and then upwards.
+pub struct FireGrid {
+    width: usize,
+    height: usize,
+    max_intensity: u32,
+    intensities: Vec<u32>,
+    rng: Pcg32,
+    decay: u32,
+    wind: i32,
+}
+
+impl FireGrid {
+    pub fn new(width: usize, height: usize, max_intensity: u32, seed: u64) -> Self {
+        let mut grid = FireGrid {
+            width,
+            height,
+            max_intensity,
+            intensities: vec![0; width * height],
+            rng: Pcg32::new(seed),
+            decay: DEFAULT_DECAY,
+            wind: 0,
+        };
+        grid.ignite();
+        grid
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn max_intensity(&self) -> u32 {
+        self.max_intensity
+    }
+
+    pub fn intensities(&self) -> &[u32] {
+        &self.intensities
+    }
+
+    pub fn intensity(&self, x: usize, y: usize) -> u32 {
+        self.intensities[self.index(x, y)]
+    }
+
+    pub fn index(&self, x: usize, y: usize) -> usize {
+        x + y * self.width
+    }
+
+    /// Reallocates the grid for new dimensions, dropping the current flames
+    /// and re-seeding the source row.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        if width == self.width && height == self.height {
+            return;
+        }
+        self.width = width;
+        self.height = height;
+        self.intensities = vec![0; width * height];
+        self.ignite();
+    }
+
+    pub fn decay(&self) -> u32 {
+        self.decay
+    }
+
+    /// Maximum intensity lost per row; every step draws a decay in `0..=decay`.
+    pub fn set_decay(&mut self, decay: u32) {
+        self.decay = decay;
+    }
+
+    pub fn wind(&self) -> i32 {
+        self.wind
+    }
+
+    /// Lateral drift in cells per row. Negative values blow to the left,
+    /// positive to the right and the magnitude bounds the random offset.
+    pub fn set_wind(&mut self, wind: i32) {
+        self.wind = wind;
+    }
+
+    pub fn is_lit(&self) -> bool {
+        self.intensities[..self.width].iter().any(|&i| i > 0)
+    }
+
+    pub fn ignite(&mut self) {
+        let max_intensity = self.max_intensity;
+        self.set_source(max_intensity);
+    }
+
+    pub fn extinguish(&mut self) {
+        self.set_source(0);
+    }
+
+    fn set_source(&mut self, intensity: u32) {
+        for x in 0..self.width {
+            self.intensities[x] = intensity;
+        }
+    }
+
+    pub fn step(&mut self) {
+        for y in 1..self.height {
+            for x in 0..self.width {
+                self.spread_fire(x, y);
+            }
+        }
+    }
+
+    fn spread_fire(&mut self, x: usize, y: usize) {
+        let down_intensity = self.intensity(x, y - 1);
+        let decay = self.rng.below(self.decay + 1);
+        let offset = self.wind.signum() * self.rng.below(self.wind.abs() as u32 + 1) as i32;
+        let dst_x = (x as i32 + offset).rem_euclid(self.width as i32) as usize;
+        let dst = self.index(dst_x, y);
+        self.intensities[dst] = down_intensity.saturating_sub(decay);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX: u32 = 35;
+
+    fn run(grid: &mut FireGrid, steps: usize) {
+        for _ in 0..steps {
+            grid.step();
+        }
+    }
+
+    #[test]
+    fn source_row_stays_at_max_intensity() {
+        let mut grid = FireGrid::new(40, 30, MAX, 1);
+        grid.set_wind(2);
+        run(&mut grid, 100);
+        assert!((0..grid.width()).all(|x| grid.intensity(x, 0) == MAX));
+    }
+
+    #[test]
+    fn flames_die_out_after_extinguish() {
+        let mut grid = FireGrid::new(40, 30, MAX, 2);
+        grid.set_wind(-3);
+        run(&mut grid, 100);
+        assert!(grid.intensity(20, 10) > 0);
+        grid.extinguish();
+        assert!(!grid.is_lit());
+        run(&mut grid, 100);
+        assert!(grid.intensities().iter().all(|&i| i == 0));
+    }
+
+    /// Intensity weighted mean column of rows 5 to 11 of a fire burning
+    /// only in the middle of the source row.
+    fn flame_centre(wind: i32) -> f64 {
+        let mut grid = FireGrid::new(80, 30, MAX, 3);
+        grid.set_wind(wind);
+        for x in 0..grid.width() {
+            grid.intensities[x] = if (35..45).contains(&x) { MAX } else { 0 };
+        }
+        run(&mut grid, 50);
+        let (mut sum, mut total) = (0.0, 0.0);
+        for y in 5..12 {
+            for x in 0..grid.width() {
+                let i = grid.intensity(x, y) as f64;
+                sum += x as f64 * i;
+                total += i;
+            }
+        }
+        sum / total
+    }
+
+    #[test]
+    fn wind_blows_the_flames_sideways() {
+        let still = flame_centre(0);
+        assert!((still - 39.5).abs() < 2.0, "still flames centred at {}", still);
+        assert!(flame_centre(3) > still + 5.0);
+        assert!(flame_centre(-3) < still - 5.0);
+    }
+}
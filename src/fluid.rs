@@ -1,4 +1,10 @@
 use nannou::prelude::*;
+use nannou::ui::prelude::*;
+use doom_fire::pixels::{PixelBuffer, PixelTexture};
+use doom_fire::fluid_solver::{Boundary, FluidSolver};
+use doom_fire::mac_fluid::MacFluid;
+use doom_fire::stable_fluid::Fluid;
+use std::path::Path;
 
 fn main() {
     nannou::app(model)
@@ -9,271 +15,510 @@ fn main() {
 
 const N: usize = 100;
 const HALF_N: f32 = N as f32 * 0.5;
-const NN: usize = N * N;
-const N_FLOAT: f32 = N as f32;
-const ITER: usize = 4;
 const SCL: f32 = 5.0;
 const N_SCL: f32 = N as f32 * SCL;
-const HALF_N_SCL: f32 = N_SCL * 0.5;
-const HALF_SCL: f32 = SCL * 0.5;
-
-struct Fluid {
-    dt: f32,
-    diff: f32,
-    visc: f32,
-    s: Vec<f32>,
-    density: Vec<f32>,
-    vx: Vec<f32>,
-    vy: Vec<f32>,
-    vx0: Vec<f32>,
-    vy0: Vec<f32>,
-}
-impl Fluid {
-    pub fn new(dt: f32, diff: f32, visc: f32) -> Self {
-        Fluid {
-            dt,
-            diff,
-            visc,
-            s: vec![0.0f32; NN],
-            density: vec![0.0f32; NN],
-            vx: vec![0.0f32; NN],
-            vy: vec![0.0f32; NN],
-            vx0: vec![0.0f32; NN],
-            vy0: vec![0.0f32; NN],
-        }
+/// Dye taken from every cell each frame, densities running from 0 to 100.
+const DECAY: f32 = 0.5;
+const BRUSH: f32 = 2.0;
+const TUNNEL_SPEED: f32 = 2.0;
+const SOLID_COLOR: [u8; 4] = [90, 70, 50, 255];
+const HUE_SPEED: f32 = 0.002;
+const AMBIENT: f32 = 0.0;
+const COOLING: f32 = 0.2;
+const HEAT: f32 = 50.0;
+const LATTICE: usize = 5;
+const ARROW_SCALE: f32 = 20.0;
+const STREAMLINE_SEEDS: usize = 10;
+const STREAMLINE_STEPS: usize = 40;
+// Colour each dye channel contributes when mixing
+const CHANNEL_COLORS: [[f32; 3]; 3] = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+widget_ids! {
+    struct Ids {
+        title,
+        vort_label,
+        vort_slider,
+        buoy_label,
+        buoy_slider,
+        ambient_label,
+        ambient_slider,
+        cooling_label,
+        cooling_slider,
     }
+}
 
-    fn add_density(&mut self, x: usize, y: usize, ammount: f32) {
-        let index = x_y_to_index(x, y);
-        self.density[index] += ammount;
-    }
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum DisplayMode {
+    Dye,
+    Velocity,
+    Streamlines,
+    Curl,
+    Divergence,
+    Pressure,
+}
 
-    fn add_velocity(&mut self, x: usize, y: usize, x_ammount: f32, y_ammount: f32) {
-        let index = x_y_to_index(x, y);
-        self.vx[index] += x_ammount;
-        self.vy[index] += y_ammount;
-    }
+struct Model {
+    ui: Ui,
+    ids: Ids,
+    mode: DisplayMode,
+    // Every solver receives the same inputs, only the active one is shown
+    solvers: Vec<Box<dyn FluidSolver>>,
+    active: usize,
+    pixels: PixelBuffer,
+    texture: PixelTexture,
+    brush: bool,
+    tunnel: bool,
+    hue: f32,
+    cycle_hue: bool,
+}
 
-    fn step(&mut self) {
-        let dt = self.dt;
-        let visc = self.visc;
-        let diff = self.diff;
-        let vx = &mut self.vx;
-        let vx0 = &mut self.vx0;
-        let vy = &mut self.vy;
-        let vy0 = &mut self.vy0;
-        let s = &mut self.s;
-        let density = &mut self.density;
+fn model(app: &App) -> Model {
+    let s = N as f32 * SCL;
+    let window = app
+        .new_window()
+        .size(s as u32, s as u32)
+        .view(view)
+        .mouse_pressed(mouse_pressed)
+        .mouse_moved(mouse_moved)
+        .key_pressed(key_pressed)
+        .build()
+        .unwrap();
 
-        diffuse(1, vx0, vx, visc, dt);
-        diffuse(2, vy0, vy, visc, dt);
+    let ui_window = app.new_window()
+        .title(app.exe_name().unwrap() + " controls")
+        .size(300, 240)
+        .view(ui_view)
+        .event(ui_event)
+        .key_pressed(key_pressed)
+        .build()
+        .unwrap();
 
-        project(vx0, vy0, vx, vy);
+    let mut ui = app.new_ui().window(ui_window).build().unwrap();
+    let ids = Ids::new(ui.widget_id_generator());
+
+    ui.clear_with(nannou::ui::prelude::color::DARK_CHARCOAL);
+    let mut theme = ui.theme_mut();
+    theme.label_color = nannou::ui::prelude::color::WHITE;
+    theme.shape_color = nannou::ui::prelude::color::CHARCOAL;
+
+    let mut solvers: Vec<Box<dyn FluidSolver>> = vec![
+        Box::new(Fluid::new(N, CHANNEL_COLORS.len(), 0.1, 0.000001, 0.000001)),
+        Box::new(MacFluid::new(N, CHANNEL_COLORS.len(), 0.1, random::<u64>())),
+    ];
+    let obstacles = std::env::args().nth(1);
+    for fluid in solvers.iter_mut() {
+        if let Some(path) = &obstacles {
+            if let Err(error) = fluid.load_solids(Path::new(path)) {
+                eprintln!("Could not load obstacles {:?}: {}", path, error);
+            }
+        }
+        fluid.set_ambient(AMBIENT);
+        fluid.set_cooling(COOLING);
+    }
 
-        advect(1, vx, vx0, vx0, vy0, dt);
-        advect(2, vy, vy0, vx0, vy0, dt);
+    let mut the_model = Model {
+        ui,
+        ids,
+        mode: DisplayMode::Dye,
+        solvers,
+        active: 0,
+        pixels: PixelBuffer::new(N, N),
+        texture: PixelTexture::new(&app.window(window).unwrap(), N, N),
+        brush: false,
+        tunnel: false,
+        hue: 0.0,
+        cycle_hue: true,
+    };
+
+    // Send a fake ui_event to draw widgets
+    ui_event(&app, &mut the_model, WindowEvent::Focused);
+
+    the_model
+}
 
-        project(vx, vy, vx0, vy0);
+fn hue_to_rgb(hue: f32) -> [f32; 3] {
+    let h = hue.fract() * 6.0;
+    [
+        clamp((h - 3.0).abs() - 1.0, 0.0, 1.0),
+        clamp(2.0 - (h - 2.0).abs(), 0.0, 1.0),
+        clamp(2.0 - (h - 4.0).abs(), 0.0, 1.0),
+    ]
+}
 
-        diffuse(0, s, density, diff, dt);
-        advect(0, density, s, vx, vy, dt);
+fn add_colored_dye(fluid: &mut dyn FluidSolver, x: usize, y: usize, rgb: [f32; 3], amount: f32) {
+    for (channel, weight) in rgb.iter().enumerate() {
+        fluid.add_dye(x, y, channel, weight * amount);
     }
+}
 
-    fn decay(&mut self) {
-        let density = &mut self.density;
-        for i in 0..N {
-            let delta = density[i] - 50.0;
-            density[i] = clamp(delta, 0.0, 100.0)
+fn mix_dyes(fluid: &dyn FluidSolver, index: usize) -> [u8; 4] {
+    let mut rgb = [0.0; 3];
+    for (channel, color) in CHANNEL_COLORS.iter().enumerate() {
+        let amount = fluid.dye(channel)[index] / 100.0;
+        for c in 0..3 {
+            rgb[c] += color[c] * amount;
         }
     }
+    let to_u8 = |v: f32| (clamp(v, 0.0, 1.0) * 255.0) as u8;
+    [to_u8(rgb[0]), to_u8(rgb[1]), to_u8(rgb[2]), 255]
 }
 
-fn x_y_to_index(x: usize, y: usize) -> usize {
-    let xc = clamp(x, 0, N -1);
-    let yc = clamp(y, 0, N -1);
-    xc + yc * N
+fn mouse_to_grid(app: &App) -> (f32, f32) {
+    (HALF_N + app.mouse.x / SCL, HALF_N + app.mouse.y / SCL)
 }
 
-fn set_bnd(b: usize, x: &mut Vec<f32>) {
-    for i in 1..N-1 {
-        x[x_y_to_index(i, 0)] = x[x_y_to_index(i, 1)] * (if b == 2 { -1.0 } else { 1.0 });
-        x[x_y_to_index(i, N-1)] = x[x_y_to_index(i, N-2)] * (if b == 2 { -1.0 } else { 1.0 });
-    }
-    for j in 1..N-1 {
-        x[x_y_to_index(0, j)] = x[x_y_to_index(1, j)] * (if b == 1 { -1.0 } else { 1.0 });
-        x[x_y_to_index(N-1, j)] = x[x_y_to_index(N-2, j)] * (if b == 1 { -1.0 } else { 1.0 });
-    }
-    x[x_y_to_index(0, 0)] = 0.33 * (x[x_y_to_index(1, 0)] + x[x_y_to_index(0, 1)]);
-    x[x_y_to_index(0, N-1)] = 0.33 * (x[x_y_to_index(1, N-1)] + x[x_y_to_index(0, N-2)]);
-    x[x_y_to_index(N-1, 0)] = 0.33 * (x[x_y_to_index(N-2, 0)] + x[x_y_to_index(N-1, 1)]);
-    x[x_y_to_index(N-1, N-1)] = 0.33 * (x[x_y_to_index(N-2, N-1)] + x[x_y_to_index(N-1, N-2)]);
-
+fn grid_to_screen(x: f32, y: f32) -> Point2 {
+    pt2((x + 0.5 - HALF_N) * SCL, (y + 0.5 - HALF_N) * SCL)
 }
 
-
-fn linear_solve(b: usize, x: &mut Vec<f32>, x0: &mut Vec<f32>, a: f32, c: f32) {
-    let c_recip = 1.0 / c;
-    for _k in 0..ITER {
-        for j in 1..N-1 {
-            for i in 1..N-1 {
-                let index = x_y_to_index(i, j);
-                x[index] = (x0[index] + a * (
-                    x[x_y_to_index(i + 1, j)] +
-                    x[x_y_to_index(i - 1, j)] +
-                    x[x_y_to_index(i, j + 1)] +
-                    x[x_y_to_index(i, j - 1)]
-                )) * c_recip;
-            }
-        }
-        set_bnd(b, x);
+fn heat_color(v: f32, max: f32) -> [u8; 4] {
+    let t = if max > 0.0 { clamp(v / max, -1.0, 1.0) } else { 0.0 };
+    if t >= 0.0 {
+        [(t * 255.0) as u8, (t * 80.0) as u8, 0, 255]
+    } else {
+        [0, (-t * 80.0) as u8, (-t * 255.0) as u8, 255]
     }
 }
 
-fn diffuse(b: usize, x: &mut Vec<f32>, x0: &mut Vec<f32>, diff: f32, dt: f32) {
-    let n_minus_2 = (N - 2) as f32;
-    let a = dt * diff * n_minus_2 * n_minus_2;
-    linear_solve(b, x, x0, a, 1.0 + 6.0 * a);
+fn fill_heat_map(pixels: &mut PixelBuffer, field: &[f32]) {
+    let max = field.iter().fold(0.0f32, |m, v| m.max(v.abs()));
+    pixels.fill_from(field, |&v| heat_color(v, max));
 }
 
-fn project(vx: &mut Vec<f32>, vy: &mut Vec<f32>, p: &mut Vec<f32>, div: &mut Vec<f32>) {
-    for j in 1..N-1 {
-        for i in 1..N-1 {
-            div[x_y_to_index(i, j)] = -0.5 * (
-                vx[x_y_to_index(i + 1, j)] -
-                vx[x_y_to_index(i - 1, j)] +
-                vy[x_y_to_index(i, j + 1)] -
-                vy[x_y_to_index(i, j - 1)]
-            ) / N as f32;
-            p[x_y_to_index(i, j)] = 0.0;
+fn draw_velocity(draw: &Draw, fluid: &dyn FluidSolver) {
+    for j in (LATTICE / 2..N).step_by(LATTICE) {
+        for i in (LATTICE / 2..N).step_by(LATTICE) {
+            let (vx, vy) = fluid.velocity_at(i as f32, j as f32);
+            let v = (vec2(vx, vy) * ARROW_SCALE).limit_magnitude(LATTICE as f32 * SCL);
+            if v.magnitude() < 1.0 {
+                continue;
+            }
+            let start = grid_to_screen(i as f32, j as f32);
+            draw.arrow()
+                .start(start)
+                .end(start + v)
+                .weight(1.0)
+                .head_length(3.0)
+                .head_width(2.0)
+                .color(WHITE);
         }
     }
-    set_bnd(0, div);
-    set_bnd(0, p);
-    linear_solve(0, p, div, 1.0, 6.0);
-    for j in 1..N-1 {
-        for i in 1..N-1 {
-            vx[x_y_to_index(i, j)] -= 0.5 * (p[x_y_to_index(i + 1, j)] - p[x_y_to_index(i - 1, j)]) * N as f32;
-            vy[x_y_to_index(i, j)] -= 0.5 * (p[x_y_to_index(i, j + 1)] - p[x_y_to_index(i, j - 1)]) * N as f32;
+}
+
+fn draw_streamlines(draw: &Draw, fluid: &dyn FluidSolver) {
+    let spacing = N as f32 / STREAMLINE_SEEDS as f32;
+    for sj in 0..STREAMLINE_SEEDS {
+        for si in 0..STREAMLINE_SEEDS {
+            let mut p = vec2((si as f32 + 0.5) * spacing, (sj as f32 + 0.5) * spacing);
+            let mut points = vec![grid_to_screen(p.x, p.y)];
+            for _ in 0..STREAMLINE_STEPS {
+                let (vx, vy) = fluid.velocity_at(p.x, p.y);
+                let v = vec2(vx, vy);
+                if v.magnitude2() < 1e-8 {
+                    break;
+                }
+                p += v.normalize() * 0.5;
+                if p.x < 0.0 || p.y < 0.0 || p.x > N as f32 - 1.0 || p.y > N as f32 - 1.0 {
+                    break;
+                }
+                points.push(grid_to_screen(p.x, p.y));
+            }
+            if points.len() > 1 {
+                draw.polyline().weight(1.0).points(points).color(srgba(1.0, 1.0, 1.0, 0.6));
+            }
         }
     }
-    set_bnd(1, vx);
-    set_bnd(2, vy);
 }
 
-fn advect(b: usize, d: &mut Vec<f32>, d0: & Vec<f32>, vx: & Vec<f32>, vy: & Vec<f32>, dt: f32) {
-    let (mut i0, mut i1, mut j0, mut j1): (f32, f32, f32, f32);
-
-    let dtx = dt * (N - 2) as f32;
-    let dty = dt * (N - 2) as f32;
-
-    let (mut tmp1, mut tmp2,  mut x, mut y): (f32, f32, f32, f32);
-
-    let (mut s1, mut s0, mut t1, mut t0): (f32, f32, f32,f32);
-
-    for j in 1..N-1 {
-        let j_float = j as f32;
-        for i in 1..N-1 {
-            let i_float = i as f32;
-            tmp1 = dtx * vx[x_y_to_index(i, j)];
-            tmp2 = dty * vy[x_y_to_index(i, j)];
-            x = i_float - tmp1;
-            y = j_float - tmp2;
-            if x < 0.5 { x = 0.5; }
-            if x > 0.5 + N_FLOAT { x = 0.5 + N_FLOAT; }
-            i0 = x.floor();
-            i1 = i0 + 1.0;
-            if y < 0.5 { y = 0.5; }
-            if y > 0.5 + N_FLOAT { y = 0.5 + N_FLOAT; }
-            j0 = y.floor();
-            j1 = j0 + 1.0;
-
-            s1 = x - i0;
-            s0 = 1.0 - s1;
-            t1 = y - j0;
-            t0 = 1.0 - t1;
-
-            let i0i = i0 as usize;
-            let i1i = i1 as usize;
-            let j0i = j0 as usize;
-            let j1i = j1 as usize;
-
-            d[x_y_to_index(i, j)] =
-                s0 * (t0 * d0[x_y_to_index(i0i, j0i)] + t1 * d0[x_y_to_index(i0i, j1i)]) +
-                s1 * (t0 * d0[x_y_to_index(i1i, j0i)] + t1 * d0[x_y_to_index(i1i, j1i)]);
-
+fn paint_solids(fluid: &mut dyn FluidSolver, cx: f32, cy: f32, solid: bool) {
+    let r = BRUSH as i32;
+    for dy in -r..=r {
+        for dx in -r..=r {
+            let x = cx as i32 + dx;
+            let y = cy as i32 + dy;
+            if x >= 0 && y >= 0 && dx * dx + dy * dy <= r * r {
+                fluid.set_solid(x as usize, y as usize, solid);
+            }
         }
     }
-    set_bnd(b, d);
 }
 
-struct Model {
-    fluid: Fluid
+fn add_airfoil(fluid: &mut dyn FluidSolver, x0: f32, y0: f32, chord: f32, thickness: f32) {
+    // Symmetric NACA 00xx profile
+    for i in 0..=chord as usize {
+        let t = i as f32 / chord;
+        let half = 5.0 * thickness * chord
+            * (0.2969 * t.sqrt() - 0.1260 * t - 0.3516 * t * t + 0.2843 * t.powi(3) - 0.1015 * t.powi(4));
+        let x = (x0 + i as f32) as usize;
+        let y_min = (y0 - half).round() as i32;
+        let y_max = (y0 + half).round() as i32;
+        for y in y_min..=y_max {
+            fluid.set_solid(x, y.max(0) as usize, true);
+        }
+    }
 }
 
-fn model(app: &App) -> Model {
-    let s = N as f32 * SCL;
-    let _window = app
-        .new_window()
-        .size(s as u32, s as u32)
-        .view(view)
-        .mouse_pressed(mouse_pressed)
-        .mouse_moved(mouse_moved)
-        .build()
-        .unwrap();
-
-    Model {
-        fluid: Fluid::new(0.1, 0.000001, 0.000001)
+fn key_pressed(_app: &App, model: &mut Model, key: Key) {
+    let solvers = &mut model.solvers;
+    match key {
+        Key::O => {
+            model.brush = !model.brush;
+        }
+        Key::T => {
+            model.tunnel = !model.tunnel;
+        }
+        Key::M => {
+            model.active = (model.active + 1) % solvers.len();
+            println!("{} solver", solvers[model.active].name());
+        }
+        Key::B => {
+            let boundary = match solvers[model.active].boundary() {
+                Boundary::NoSlip => Boundary::FreeSlip,
+                Boundary::FreeSlip => Boundary::NoSlip,
+            };
+            println!("{:?} boundary", boundary);
+            for fluid in solvers.iter_mut() {
+                fluid.set_boundary(boundary);
+            }
+        }
+        Key::C => {
+            for fluid in solvers.iter_mut() {
+                fluid.add_disc(N as f32 * 0.3, HALF_N, N as f32 * 0.08);
+            }
+        }
+        Key::A => {
+            for fluid in solvers.iter_mut() {
+                add_airfoil(fluid.as_mut(), N as f32 * 0.25, HALF_N, N as f32 * 0.4, 0.12);
+            }
+        }
+        Key::Delete => {
+            for fluid in solvers.iter_mut() {
+                fluid.clear_solids();
+            }
+        }
+        Key::D => {
+            model.mode = DisplayMode::Dye;
+        }
+        Key::V => {
+            model.mode = DisplayMode::Velocity;
+        }
+        Key::S => {
+            model.mode = DisplayMode::Streamlines;
+        }
+        Key::W => {
+            model.mode = DisplayMode::Curl;
+        }
+        Key::X => {
+            model.mode = DisplayMode::Divergence;
+        }
+        Key::P => {
+            model.mode = DisplayMode::Pressure;
+        }
+        Key::Key0 => {
+            model.cycle_hue = true;
+        }
+        Key::Key1 => {
+            model.cycle_hue = false;
+            model.hue = 0.0;
+        }
+        Key::Key2 => {
+            model.cycle_hue = false;
+            model.hue = 1.0 / 3.0;
+        }
+        Key::Key3 => {
+            model.cycle_hue = false;
+            model.hue = 2.0 / 3.0;
+        }
+        _other_key => {}
     }
 }
 
-fn mouse_pressed(app: &App, model: &mut Model, _button: MouseButton) {
-    let x = HALF_N + app.mouse.x / SCL;
-    let y = HALF_N + app.mouse.y / SCL;
-    let fluid = &mut model.fluid;
-    fluid.add_density(x as usize, y as usize, 0.0);
-    fluid.add_velocity(x as usize, y as usize, (random::<f32>() - 0.5) * 100.0, (random::<f32>() - 0.5) * 100.0);
+fn mouse_pressed(app: &App, model: &mut Model, button: MouseButton) {
+    let (x, y) = mouse_to_grid(app);
+    if model.brush {
+        for fluid in model.solvers.iter_mut() {
+            paint_solids(fluid.as_mut(), x, y, button == MouseButton::Left);
+        }
+        return;
+    }
+    let (vx, vy) = ((random::<f32>() - 0.5) * 100.0, (random::<f32>() - 0.5) * 100.0);
+    for fluid in model.solvers.iter_mut() {
+        fluid.add_velocity(x as usize, y as usize, vx, vy);
+    }
 }
 
 
 fn mouse_moved(app: &App, model: &mut Model, _dir: Vector2<f32>) {
+    let (x, y) = mouse_to_grid(app);
+    if model.brush {
+        for fluid in model.solvers.iter_mut() {
+            if app.mouse.buttons.left().is_down() {
+                paint_solids(fluid.as_mut(), x, y, true);
+            }
+            if app.mouse.buttons.right().is_down() {
+                paint_solids(fluid.as_mut(), x, y, false);
+            }
+        }
+        return;
+    }
     if app.mouse.buttons.left().is_down() {
-        let x = HALF_N + app.mouse.x / SCL;
-        let y = HALF_N + app.mouse.y / SCL;
-        let fluid = &mut model.fluid;
-        fluid.add_density(x as usize, y as usize, random::<f32>() * 100.0);
-        fluid.add_velocity(x as usize, y as usize, (random::<f32>() - 0.5) * 10.0, (random::<f32>() - 0.5) * 10.0);
+        let rgb = hue_to_rgb(model.hue);
+        let amount = random::<f32>() * 100.0;
+        let (vx, vy) = ((random::<f32>() - 0.5) * 10.0, (random::<f32>() - 0.5) * 10.0);
+        for fluid in model.solvers.iter_mut() {
+            add_colored_dye(fluid.as_mut(), x as usize, y as usize, rgb, amount);
+            fluid.add_temperature(x as usize, y as usize, HEAT);
+            fluid.add_velocity(x as usize, y as usize, vx, vy);
+        }
     }
     if app.mouse.buttons.right().is_down() {
-        let fluid = &mut model.fluid;
-        for i in 0..N {
-            fluid.s[i] = 0.0;
-            fluid.density[i] = 0.0;
-            fluid.vx[i] = 0.0;
-            fluid.vy[i] = 0.0;
-            fluid.vx0[i] = 0.0;
-            fluid.vy0[i] = 0.0;
+        for fluid in model.solvers.iter_mut() {
+            fluid.clear();
         }
     }
 }
 
 fn update(_app: &App, model: &mut Model, _update: Update) {
-    let fluid = &mut model.fluid;
-    fluid.decay();
-    fluid.step();
+    for fluid in model.solvers.iter_mut() {
+        if model.tunnel {
+            for j in 1..N - 1 {
+                fluid.add_velocity(1, j, TUNNEL_SPEED, 0.0);
+                if j % 8 == 0 {
+                    fluid.add_dye(1, j, (j / 8) % fluid.channels(), 100.0);
+                }
+            }
+        }
+        fluid.decay(DECAY);
+        fluid.step();
+    }
+    if model.cycle_hue {
+        model.hue = (model.hue + HUE_SPEED).fract();
+    }
+    let fluid = model.solvers[model.active].as_ref();
+    match model.mode {
+        DisplayMode::Dye | DisplayMode::Velocity | DisplayMode::Streamlines => {
+            for index in 0..N * N {
+                model.pixels.set(index % N, index / N, mix_dyes(fluid, index));
+            }
+        }
+        DisplayMode::Curl => fill_heat_map(&mut model.pixels, &fluid.curl()),
+        DisplayMode::Divergence => fill_heat_map(&mut model.pixels, &fluid.divergence()),
+        DisplayMode::Pressure => fill_heat_map(&mut model.pixels, fluid.pressure()),
+    }
+    for (index, &solid) in fluid.solids().iter().enumerate() {
+        if solid {
+            model.pixels.set(index % N, index / N, SOLID_COLOR);
+        }
+    }
 }
 
 fn view(app: &App, model: &Model, frame: Frame){
     let draw = app.draw();
     draw.background().color(BLACK);
-    let fluid = & model.fluid;
-    for j in 0..N {
-        for i in 0..N {
-            let index = x_y_to_index(i, j);
-            let density = fluid.density[index];
-            let x = i as f32 * SCL - HALF_N_SCL + HALF_SCL;
-            let y = j as f32 * SCL - HALF_N_SCL + HALF_SCL;
-            draw.rect().x_y(x, y).w(SCL).h(SCL).color(srgba(1.0, 1.0, 1.0, density / 100.0));
-        }
+    model.texture.upload(app, &frame, &model.pixels);
+    model.texture.draw(&draw, Rect::from_w_h(N_SCL, N_SCL));
+    let fluid = model.solvers[model.active].as_ref();
+    match model.mode {
+        DisplayMode::Velocity => draw_velocity(&draw, fluid),
+        DisplayMode::Streamlines => draw_streamlines(&draw, fluid),
+        _ => {}
+    }
+    draw.text(&format!("{:?} - {}", model.mode, fluid.name()))
+        .x_y(-N_SCL * 0.5 + 100.0, N_SCL * 0.5 - 15.0)
+        .w(200.0)
+        .left_justify()
+        .color(WHITE);
+    // Total dye left in each solver, to compare their numerical dissipation
+    for (k, solver) in model.solvers.iter().enumerate() {
+        let total: f32 = (0..solver.channels()).map(|c| solver.total_dye(c)).sum();
+        draw.text(&format!("{}: {:.0}", solver.name(), total))
+            .x_y(-N_SCL * 0.5 + 100.0, N_SCL * 0.5 - 35.0 - 15.0 * k as f32)
+            .w(200.0)
+            .left_justify()
+            .color(WHITE);
     }
     draw.to_frame(app, &frame).unwrap()
-}
\ No newline at end of file
+}
+
+fn ui_event(_app: &App, model: &mut Model, _event: WindowEvent) {
+    let ui = &mut model.ui.set_widgets();
+    let solvers = &mut model.solvers;
+    let active = &solvers[model.active];
+    let (vorticity, buoyancy, ambient, cooling) =
+        (active.vorticity(), active.buoyancy(), active.ambient(), active.cooling());
+
+    // Control panel title
+    widget::Text::new("Fluid Forces")
+        .top_left_with_margin(10.0)
+        .w_h(300.0, 40.0)
+        .font_size(24)
+        .set(model.ids.title, ui);
+
+    widget::Text::new("Vorticity")
+        .down_from(model.ids.title, 15.0)
+        .w_h(125.0, 30.0)
+        .set(model.ids.vort_label, ui);
+
+    for value in widget::Slider::new(vorticity, 0.0, 2.0)
+        .right_from(model.ids.vort_label, 10.0)
+        .w_h(150.0, 30.0)
+        .label(&vorticity.to_string())
+        .set(model.ids.vort_slider, ui)
+    {
+        for fluid in solvers.iter_mut() {
+            fluid.set_vorticity(value);
+        }
+    }
+
+    widget::Text::new("Buoyancy")
+        .down_from(model.ids.vort_label, 10.0)
+        .w_h(125.0, 30.0)
+        .set(model.ids.buoy_label, ui);
+
+    for value in widget::Slider::new(buoyancy, 0.0, 0.05)
+        .right_from(model.ids.buoy_label, 10.0)
+        .w_h(150.0, 30.0)
+        .label(&buoyancy.to_string())
+        .set(model.ids.buoy_slider, ui)
+    {
+        for fluid in solvers.iter_mut() {
+            fluid.set_buoyancy(value);
+        }
+    }
+
+    widget::Text::new("Ambient temp.")
+        .down_from(model.ids.buoy_label, 10.0)
+        .w_h(125.0, 30.0)
+        .set(model.ids.ambient_label, ui);
+
+    for value in widget::Slider::new(ambient, 0.0, 50.0)
+        .right_from(model.ids.ambient_label, 10.0)
+        .w_h(150.0, 30.0)
+        .label(&ambient.to_string())
+        .set(model.ids.ambient_slider, ui)
+    {
+        for fluid in solvers.iter_mut() {
+            fluid.set_ambient(value);
+        }
+    }
+
+    widget::Text::new("Cooling")
+        .down_from(model.ids.ambient_label, 10.0)
+        .w_h(125.0, 30.0)
+        .set(model.ids.cooling_label, ui);
+
+    for value in widget::Slider::new(cooling, 0.0, 1.0)
+        .right_from(model.ids.cooling_label, 10.0)
+        .w_h(150.0, 30.0)
+        .label(&cooling.to_string())
+        .set(model.ids.cooling_slider, ui)
+    {
+        for fluid in solvers.iter_mut() {
+            fluid.set_cooling(value);
+        }
+    }
+}
+
+fn ui_view(app: &App, model: &Model, frame: Frame) {
+    model.ui.draw_to_frame_if_changed(app, &frame).unwrap();
+}
@@ -0,0 +1,41 @@
+use doom_fire::stable_fluid::Fluid;
+use std::time::Instant;
+
+// Run with `cargo run --release --example fluid_bench`
+const SIZES: [usize; 3] = [100, 256, 512];
+const WARMUP: usize = 3;
+const STEPS: usize = 20;
+
+fn seeded_fluid(n: usize, parallel: bool) -> Fluid {
+    let mut fluid = Fluid::new(n, 1, 0.1, 0.000001, 0.000001);
+    fluid.set_parallel(parallel);
+    let c = n / 2;
+    for j in c - n / 8..c + n / 8 {
+        for i in c - n / 8..c + n / 8 {
+            fluid.add_dye(i, j, 0, 100.0);
+            fluid.add_velocity(i, j, 1.0, 0.5);
+        }
+    }
+    fluid
+}
+
+fn time_steps(n: usize, parallel: bool) -> f64 {
+    let mut fluid = seeded_fluid(n, parallel);
+    for _ in 0..WARMUP {
+        fluid.step();
+    }
+    let start = Instant::now();
+    for _ in 0..STEPS {
+        fluid.step();
+    }
+    start.elapsed().as_secs_f64() * 1000.0 / STEPS as f64
+}
+
+fn main() {
+    println!("{:>6} {:>14} {:>14} {:>8}", "N", "serial ms", "parallel ms", "speedup");
+    for &n in SIZES.iter() {
+        let serial = time_steps(n, false);
+        let parallel = time_steps(n, true);
+        println!("{:>6} {:>14.3} {:>14.3} {:>7.2}x", n, serial, parallel, serial / parallel);
+    }
+}
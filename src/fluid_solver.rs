@@ -0,0 +1,79 @@
+use nannou::image::{self, imageops::FilterType, ImageResult};
+use std::path::Path;
+
+/// How velocities behave next to interior solid cells.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Boundary {
+    /// Fluid sticks to the walls, both velocity components vanish.
+    NoSlip,
+    /// Only the component normal to the wall vanishes, fluid slides along it.
+    FreeSlip,
+}
+
+/// Common interface of the fluid solvers so the example can drive and
+/// compare them with identical inputs. Grids are `size` x `size` cells
+/// stored row by row from the bottom, the outer ring being the walls, and
+/// positions are in cell coordinates with cell `(i, j)` centred on `(i, j)`.
+pub trait FluidSolver {
+    fn name(&self) -> &'static str;
+    fn size(&self) -> usize;
+    fn channels(&self) -> usize;
+    fn dye(&self, channel: usize) -> &[f32];
+    fn add_dye(&mut self, x: usize, y: usize, channel: usize, amount: f32);
+    fn add_velocity(&mut self, x: usize, y: usize, x_amount: f32, y_amount: f32);
+    fn add_temperature(&mut self, x: usize, y: usize, amount: f32);
+    fn velocity_at(&self, x: f32, y: f32) -> (f32, f32);
+    fn curl(&self) -> Vec<f32>;
+    fn divergence(&self) -> Vec<f32>;
+    fn pressure(&self) -> &[f32];
+
+    fn solids(&self) -> &[bool];
+    fn set_solid(&mut self, x: usize, y: usize, solid: bool);
+    fn clear_solids(&mut self);
+    fn boundary(&self) -> Boundary;
+    fn set_boundary(&mut self, boundary: Boundary);
+
+    fn vorticity(&self) -> f32;
+    fn set_vorticity(&mut self, vorticity: f32);
+    fn buoyancy(&self) -> f32;
+    fn set_buoyancy(&mut self, buoyancy: f32);
+    fn ambient(&self) -> f32;
+    fn set_ambient(&mut self, ambient: f32);
+    fn cooling(&self) -> f32;
+    fn set_cooling(&mut self, cooling: f32);
+
+    fn decay(&mut self, amount: f32);
+    fn clear(&mut self);
+    fn step(&mut self);
+
+    fn total_dye(&self, channel: usize) -> f32 {
+        self.dye(channel).iter().sum()
+    }
+
+    fn add_disc(&mut self, cx: f32, cy: f32, radius: f32) {
+        for j in 1..self.size() - 1 {
+            for i in 1..self.size() - 1 {
+                let dx = i as f32 - cx;
+                let dy = j as f32 - cy;
+                if dx * dx + dy * dy <= radius * radius {
+                    self.set_solid(i, j, true);
+                }
+            }
+        }
+    }
+
+    /// Loads obstacles from an image scaled to the grid, dark pixels being solid.
+    fn load_solids(&mut self, path: &Path) -> ImageResult<()> {
+        let n = self.size() as u32;
+        let mask = image::open(path)?
+            .resize_exact(n, n, FilterType::Nearest)
+            .to_luma();
+        self.clear_solids();
+        for (x, y, pixel) in mask.enumerate_pixels() {
+            if pixel[0] < 128 {
+                self.set_solid(x as usize, (n - 1 - y) as usize, true);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,12 @@
+pub mod ant;
+pub mod args;
+pub mod csb;
+pub mod fire_grid;
+pub mod fluid_solver;
+pub mod mac_fluid;
+pub mod palette;
+pub mod physarum;
+pub mod pixels;
+pub mod rng;
+pub mod stable_fluid;
+pub mod turmite;
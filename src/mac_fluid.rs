@@ -0,0 +1,703 @@
+use crate::fluid_solver::{Boundary, FluidSolver};
+use crate::rng::Pcg32;
+
+const PARTICLES_PER_CELL: usize = 4;
+/// Particles a cell keeps before reseeding drops the extra ones.
+const MAX_PARTICLES_PER_CELL: usize = 2 * PARTICLES_PER_CELL;
+const FLIP_RATIO: f32 = 0.95;
+const PRESSURE_ITER: usize = 20;
+
+struct Particle {
+    x: f32,
+    y: f32,
+    vx: f32,
+    vy: f32,
+}
+
+/// Staggered MAC grid solver that carries momentum on particles and blends
+/// PIC and FLIP velocity updates. Horizontal velocities live on the left
+/// faces of the cells, `(size + 1) x size` of them, vertical velocities on
+/// the bottom faces, `size x (size + 1)`. Dyes and temperature stay on the
+/// cell centres and are advected semi-Lagrangian through the face velocities.
+pub struct MacFluid {
+    size: usize,
+    iter: usize,
+    dt: f32,
+    flip: f32,
+    u: Vec<f32>,
+    v: Vec<f32>,
+    u_old: Vec<f32>,
+    v_old: Vec<f32>,
+    u_weight: Vec<f32>,
+    v_weight: Vec<f32>,
+    u_impulse: Vec<f32>,
+    v_impulse: Vec<f32>,
+    pressure: Vec<f32>,
+    dyes: Vec<Vec<f32>>,
+    temperature: Vec<f32>,
+    scratch: Vec<f32>,
+    solid: Vec<bool>,
+    boundary: Boundary,
+    particles: Vec<Particle>,
+    rng: Pcg32,
+    vorticity: f32,
+    buoyancy: f32,
+    ambient: f32,
+    cooling: f32,
+}
+
+impl MacFluid {
+    pub fn new(size: usize, channels: usize, dt: f32, seed: u64) -> Self {
+        assert!(size >= 3, "fluid grid needs at least one interior cell");
+        let nn = size * size;
+        let faces = (size + 1) * size;
+        let mut fluid = MacFluid {
+            size,
+            iter: PRESSURE_ITER,
+            dt,
+            flip: FLIP_RATIO,
+            u: vec![0.0; faces],
+            v: vec![0.0; faces],
+            u_old: vec![0.0; faces],
+            v_old: vec![0.0; faces],
+            u_weight: vec![0.0; faces],
+            v_weight: vec![0.0; faces],
+            u_impulse: vec![0.0; faces],
+            v_impulse: vec![0.0; faces],
+            pressure: vec![0.0; nn],
+            dyes: vec![vec![0.0; nn]; channels],
+            temperature: vec![0.0; nn],
+            scratch: vec![0.0; nn],
+            solid: vec![false; nn],
+            boundary: Boundary::NoSlip,
+            particles: Vec::new(),
+            rng: Pcg32::new(seed),
+            vorticity: 0.0,
+            buoyancy: 0.0,
+            ambient: 0.0,
+            cooling: 0.0,
+        };
+        fluid.seed_particles();
+        fluid
+    }
+
+    /// Blend between PIC (0) and FLIP (1) velocity updates.
+    pub fn set_flip_ratio(&mut self, flip: f32) {
+        self.flip = flip.max(0.0).min(1.0);
+    }
+
+    pub fn set_iterations(&mut self, iter: usize) {
+        self.iter = iter;
+    }
+
+    pub fn particle_count(&self) -> usize {
+        self.particles.len()
+    }
+
+    fn index(&self, x: usize, y: usize) -> usize {
+        x.min(self.size - 1) + y.min(self.size - 1) * self.size
+    }
+
+    fn u_index(&self, i: usize, j: usize) -> usize {
+        i + j * (self.size + 1)
+    }
+
+    fn v_index(&self, i: usize, j: usize) -> usize {
+        i + j * self.size
+    }
+
+    /// Walls and interior obstacles.
+    fn blocked(&self, i: usize, j: usize) -> bool {
+        let n = self.size;
+        i == 0 || j == 0 || i >= n - 1 || j >= n - 1 || self.solid[i + j * n]
+    }
+
+    fn seed_particles(&mut self) {
+        self.particles.clear();
+        let n = self.size;
+        for j in 1..n - 1 {
+            for i in 1..n - 1 {
+                if !self.solid[i + j * n] {
+                    self.spawn_particles(i, j, PARTICLES_PER_CELL);
+                }
+            }
+        }
+    }
+
+    fn spawn_particles(&mut self, i: usize, j: usize, count: usize) {
+        for _ in 0..count {
+            let x = i as f32 + self.rng.range_f32(-0.5, 0.5);
+            let y = j as f32 + self.rng.range_f32(-0.5, 0.5);
+            let (vx, vy) = self.velocity_at(x, y);
+            self.particles.push(Particle { x, y, vx, vy });
+        }
+    }
+
+    fn particles_to_grid(&mut self) {
+        let n = self.size;
+        self.u_old.copy_from_slice(&self.u);
+        self.v_old.copy_from_slice(&self.v);
+        for f in [&mut self.u, &mut self.v, &mut self.u_weight, &mut self.v_weight].iter_mut() {
+            for x in f.iter_mut() {
+                *x = 0.0;
+            }
+        }
+        for p in self.particles.iter() {
+            splat(&mut self.u, &mut self.u_weight, n + 1, n, p.x + 0.5, p.y, p.vx);
+            splat(&mut self.v, &mut self.v_weight, n, n + 1, p.x, p.y + 0.5, p.vy);
+        }
+        normalize(&mut self.u, &self.u_weight, &self.u_old);
+        normalize(&mut self.v, &self.v_weight, &self.v_old);
+    }
+
+    fn apply_forces(&mut self) {
+        let n = self.size;
+        for (u, impulse) in self.u.iter_mut().zip(self.u_impulse.iter_mut()) {
+            *u += *impulse;
+            *impulse = 0.0;
+        }
+        for (v, impulse) in self.v.iter_mut().zip(self.v_impulse.iter_mut()) {
+            *v += *impulse;
+            *impulse = 0.0;
+        }
+        if self.buoyancy != 0.0 {
+            let force = self.dt * self.buoyancy;
+            for j in 1..n {
+                for i in 0..n {
+                    let t = 0.5 * (self.temperature[i + (j - 1) * n] + self.temperature[i + j * n]);
+                    let index = self.v_index(i, j);
+                    self.v[index] += force * (t - self.ambient);
+                }
+            }
+        }
+        if self.vorticity != 0.0 {
+            self.confine_vorticity();
+        }
+    }
+
+    fn confine_vorticity(&mut self) {
+        let n = self.size;
+        let curl = self.curl();
+        let force = self.dt * self.vorticity;
+        let mut fx = vec![0.0; n * n];
+        let mut fy = vec![0.0; n * n];
+        for j in 2..n - 2 {
+            for i in 2..n - 2 {
+                let index = i + j * n;
+                let grad_x = 0.5 * (curl[index + 1].abs() - curl[index - 1].abs());
+                let grad_y = 0.5 * (curl[index + n].abs() - curl[index - n].abs());
+                let len = (grad_x * grad_x + grad_y * grad_y).sqrt() + 1e-5;
+                fx[index] = force * (grad_y / len) * curl[index];
+                fy[index] = -force * (grad_x / len) * curl[index];
+            }
+        }
+        for j in 0..n {
+            for i in 1..n {
+                let index = self.u_index(i, j);
+                self.u[index] += 0.5 * (fx[i - 1 + j * n] + fx[i + j * n]);
+            }
+        }
+        for j in 1..n {
+            for i in 0..n {
+                let index = self.v_index(i, j);
+                self.v[index] += 0.5 * (fy[i + (j - 1) * n] + fy[i + j * n]);
+            }
+        }
+    }
+
+    /// Zeroes the normal velocity on faces touching a solid and, for no-slip
+    /// walls, the tangential velocity on the first layer of fluid faces.
+    fn enforce_boundaries(&mut self) {
+        let n = self.size;
+        let no_slip = self.boundary == Boundary::NoSlip;
+        for j in 0..n {
+            for i in 0..=n {
+                let left = i == 0 || self.blocked(i - 1, j);
+                let right = i == n || self.blocked(i, j);
+                let tangential = no_slip
+                    && i > 0
+                    && i < n
+                    && (j == 0
+                        || j == n - 1
+                        || self.blocked(i - 1, j - 1)
+                        || self.blocked(i, j - 1)
+                        || self.blocked(i - 1, j + 1)
+                        || self.blocked(i, j + 1));
+                if left || right || tangential {
+                    let index = self.u_index(i, j);
+                    self.u[index] = 0.0;
+                }
+            }
+        }
+        for j in 0..=n {
+            for i in 0..n {
+                let below = j == 0 || self.blocked(i, j - 1);
+                let above = j == n || self.blocked(i, j);
+                let tangential = no_slip
+                    && j > 0
+                    && j < n
+                    && (i == 0
+                        || i == n - 1
+                        || self.blocked(i - 1, j - 1)
+                        || self.blocked(i - 1, j)
+                        || self.blocked(i + 1, j - 1)
+                        || self.blocked(i + 1, j));
+                if below || above || tangential {
+                    let index = self.v_index(i, j);
+                    self.v[index] = 0.0;
+                }
+            }
+        }
+    }
+
+    fn cell_divergence(&self, i: usize, j: usize) -> f32 {
+        self.u[self.u_index(i + 1, j)] - self.u[self.u_index(i, j)] + self.v[self.v_index(i, j + 1)]
+            - self.v[self.v_index(i, j)]
+    }
+
+    fn project(&mut self) {
+        let n = self.size;
+        let div = self.divergence();
+        for p in self.pressure.iter_mut() {
+            *p = 0.0;
+        }
+        for _k in 0..self.iter {
+            for j in 1..n - 1 {
+                for i in 1..n - 1 {
+                    if self.blocked(i, j) {
+                        continue;
+                    }
+                    let mut sum = 0.0;
+                    let mut count = 0.0;
+                    for &(ni, nj) in [(i - 1, j), (i + 1, j), (i, j - 1), (i, j + 1)].iter() {
+                        if !self.blocked(ni, nj) {
+                            sum += self.pressure[ni + nj * n];
+                            count += 1.0;
+                        }
+                    }
+                    if count > 0.0 {
+                        self.pressure[i + j * n] = (sum - div[i + j * n]) / count;
+                    }
+                }
+            }
+        }
+        for j in 1..n - 1 {
+            for i in 2..n - 1 {
+                if !self.blocked(i - 1, j) && !self.blocked(i, j) {
+                    let index = self.u_index(i, j);
+                    self.u[index] -= self.pressure[i + j * n] - self.pressure[i - 1 + j * n];
+                }
+            }
+        }
+        for j in 2..n - 1 {
+            for i in 1..n - 1 {
+                if !self.blocked(i, j - 1) && !self.blocked(i, j) {
+                    let index = self.v_index(i, j);
+                    self.v[index] -= self.pressure[i + j * n] - self.pressure[i + (j - 1) * n];
+                }
+            }
+        }
+    }
+
+    fn grid_to_particles(&mut self) {
+        let n = self.size;
+        let flip = self.flip;
+        let (u, v, u_old, v_old) = (&self.u, &self.v, &self.u_old, &self.v_old);
+        for p in self.particles.iter_mut() {
+            let pic_x = sample(u, n + 1, n, p.x + 0.5, p.y);
+            let pic_y = sample(v, n, n + 1, p.x, p.y + 0.5);
+            let delta_x = pic_x - sample(u_old, n + 1, n, p.x + 0.5, p.y);
+            let delta_y = pic_y - sample(v_old, n, n + 1, p.x, p.y + 0.5);
+            p.vx = flip * (p.vx + delta_x) + (1.0 - flip) * pic_x;
+            p.vy = flip * (p.vy + delta_y) + (1.0 - flip) * pic_y;
+        }
+    }
+
+    fn advect_particles(&mut self) {
+        let dt0 = self.dt * (self.size - 2) as f32;
+        let min = 0.5;
+        let max = self.size as f32 - 1.5;
+        let mut particles = std::mem::take(&mut self.particles);
+        for p in particles.iter_mut() {
+            // Midpoint (RK2) integration through the grid velocity
+            let (vx1, vy1) = self.velocity_at(p.x, p.y);
+            let mid_x = p.x + 0.5 * dt0 * vx1;
+            let mid_y = p.y + 0.5 * dt0 * vy1;
+            let (vx2, vy2) = self.velocity_at(mid_x, mid_y);
+            let x = (p.x + dt0 * vx2).max(min).min(max);
+            let y = (p.y + dt0 * vy2).max(min).min(max);
+            if !self.blocked(x.round() as usize, y.round() as usize) {
+                p.x = x;
+                p.y = y;
+            }
+        }
+        self.particles = particles;
+    }
+
+    /// Keeps every fluid cell populated, the flow would otherwise open holes
+    /// where no particle carries momentum, and thins out crowded cells so
+    /// the particle count stays bounded where the flow converges.
+    fn reseed_particles(&mut self) {
+        let n = self.size;
+        let mut counts = vec![0usize; n * n];
+        let solid = &self.solid;
+        self.particles.retain(|p| {
+            let index = p.x.round() as usize + p.y.round() as usize * n;
+            counts[index] += 1;
+            !solid[index] && counts[index] <= MAX_PARTICLES_PER_CELL
+        });
+        for j in 1..n - 1 {
+            for i in 1..n - 1 {
+                if counts[i + j * n] == 0 && !self.solid[i + j * n] {
+                    self.spawn_particles(i, j, PARTICLES_PER_CELL);
+                }
+            }
+        }
+    }
+
+    fn advect_scalar(&mut self, field: &mut Vec<f32>) {
+        let n = self.size;
+        let dt0 = self.dt * (n - 2) as f32;
+        // Walls hold no dye, so backtraces stop on the first fluid cell
+        let max = (n - 2) as f32;
+        self.scratch.copy_from_slice(field);
+        for j in 1..n - 1 {
+            for i in 1..n - 1 {
+                let index = i + j * n;
+                if self.solid[index] {
+                    self.scratch[index] = 0.0;
+                    continue;
+                }
+                let (vx, vy) = self.velocity_at(i as f32, j as f32);
+                let x = (i as f32 - dt0 * vx).max(1.0).min(max);
+                let y = (j as f32 - dt0 * vy).max(1.0).min(max);
+                self.scratch[index] = sample(field, n, n, x, y);
+            }
+        }
+        std::mem::swap(field, &mut self.scratch);
+    }
+}
+
+fn splat(field: &mut [f32], weight: &mut [f32], w: usize, h: usize, fx: f32, fy: f32, value: f32) {
+    let fx = fx.max(0.0).min((w - 1) as f32);
+    let fy = fy.max(0.0).min((h - 1) as f32);
+    let i0 = (fx.floor() as usize).min(w - 2);
+    let j0 = (fy.floor() as usize).min(h - 2);
+    let s1 = fx - i0 as f32;
+    let t1 = fy - j0 as f32;
+    let corners = [
+        (i0, j0, (1.0 - s1) * (1.0 - t1)),
+        (i0 + 1, j0, s1 * (1.0 - t1)),
+        (i0, j0 + 1, (1.0 - s1) * t1),
+        (i0 + 1, j0 + 1, s1 * t1),
+    ];
+    for &(i, j, k) in corners.iter() {
+        field[i + j * w] += k * value;
+        weight[i + j * w] += k;
+    }
+}
+
+fn normalize(field: &mut [f32], weight: &[f32], fallback: &[f32]) {
+    for ((f, &w), &old) in field.iter_mut().zip(weight.iter()).zip(fallback.iter()) {
+        *f = if w > 1e-6 { *f / w } else { old };
+    }
+}
+
+fn sample(field: &[f32], w: usize, h: usize, fx: f32, fy: f32) -> f32 {
+    let fx = fx.max(0.0).min((w - 1) as f32);
+    let fy = fy.max(0.0).min((h - 1) as f32);
+    let i0 = (fx.floor() as usize).min(w - 2);
+    let j0 = (fy.floor() as usize).min(h - 2);
+    let s1 = fx - i0 as f32;
+    let t1 = fy - j0 as f32;
+    let k = i0 + j0 * w;
+    (1.0 - s1) * ((1.0 - t1) * field[k] + t1 * field[k + w])
+        + s1 * ((1.0 - t1) * field[k + 1] + t1 * field[k + w + 1])
+}
+
+impl FluidSolver for MacFluid {
+    fn name(&self) -> &'static str {
+        "MAC PIC/FLIP"
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    fn channels(&self) -> usize {
+        self.dyes.len()
+    }
+
+    fn dye(&self, channel: usize) -> &[f32] {
+        &self.dyes[channel]
+    }
+
+    fn add_dye(&mut self, x: usize, y: usize, channel: usize, amount: f32) {
+        let index = self.index(x, y);
+        if !self.solid[index] {
+            self.dyes[channel][index] += amount;
+        }
+    }
+
+    fn add_velocity(&mut self, x: usize, y: usize, x_amount: f32, y_amount: f32) {
+        let (x, y) = (x.min(self.size - 1), y.min(self.size - 1));
+        if self.solid[self.index(x, y)] {
+            return;
+        }
+        let (left, right) = (self.u_index(x, y), self.u_index(x + 1, y));
+        let (bottom, top) = (self.v_index(x, y), self.v_index(x, y + 1));
+        self.u_impulse[left] += 0.5 * x_amount;
+        self.u_impulse[right] += 0.5 * x_amount;
+        self.v_impulse[bottom] += 0.5 * y_amount;
+        self.v_impulse[top] += 0.5 * y_amount;
+    }
+
+    fn add_temperature(&mut self, x: usize, y: usize, amount: f32) {
+        let index = self.index(x, y);
+        if !self.solid[index] {
+            self.temperature[index] += amount;
+        }
+    }
+
+    fn velocity_at(&self, x: f32, y: f32) -> (f32, f32) {
+        let n = self.size;
+        (
+            sample(&self.u, n + 1, n, x + 0.5, y),
+            sample(&self.v, n, n + 1, x, y + 0.5),
+        )
+    }
+
+    fn curl(&self) -> Vec<f32> {
+        let n = self.size;
+        let mut curl = vec![0.0; n * n];
+        for j in 1..n - 1 {
+            for i in 1..n - 1 {
+                if self.blocked(i, j) {
+                    continue;
+                }
+                let (_, vy_r) = self.velocity_at(i as f32 + 1.0, j as f32);
+                let (_, vy_l) = self.velocity_at(i as f32 - 1.0, j as f32);
+                let (vx_t, _) = self.velocity_at(i as f32, j as f32 + 1.0);
+                let (vx_b, _) = self.velocity_at(i as f32, j as f32 - 1.0);
+                curl[i + j * n] = 0.5 * (vy_r - vy_l - vx_t + vx_b);
+            }
+        }
+        curl
+    }
+
+    fn divergence(&self) -> Vec<f32> {
+        let n = self.size;
+        let mut div = vec![0.0; n * n];
+        for j in 1..n - 1 {
+            for i in 1..n - 1 {
+                if !self.blocked(i, j) {
+                    div[i + j * n] = self.cell_divergence(i, j);
+                }
+            }
+        }
+        div
+    }
+
+    fn pressure(&self) -> &[f32] {
+        &self.pressure
+    }
+
+    fn solids(&self) -> &[bool] {
+        &self.solid
+    }
+
+    fn set_solid(&mut self, x: usize, y: usize, solid: bool) {
+        let n = self.size;
+        if x == 0 || y == 0 || x >= n - 1 || y >= n - 1 {
+            return;
+        }
+        let index = self.index(x, y);
+        let was_solid = self.solid[index];
+        self.solid[index] = solid;
+        if solid {
+            for dye in self.dyes.iter_mut() {
+                dye[index] = 0.0;
+            }
+            self.temperature[index] = 0.0;
+        } else if was_solid {
+            self.spawn_particles(x, y, PARTICLES_PER_CELL);
+        }
+    }
+
+    fn clear_solids(&mut self) {
+        for s in self.solid.iter_mut() {
+            *s = false;
+        }
+        self.seed_particles();
+    }
+
+    fn boundary(&self) -> Boundary {
+        self.boundary
+    }
+
+    fn set_boundary(&mut self, boundary: Boundary) {
+        self.boundary = boundary;
+    }
+
+    fn vorticity(&self) -> f32 {
+        self.vorticity
+    }
+
+    fn set_vorticity(&mut self, vorticity: f32) {
+        self.vorticity = vorticity;
+    }
+
+    fn buoyancy(&self) -> f32 {
+        self.buoyancy
+    }
+
+    fn set_buoyancy(&mut self, buoyancy: f32) {
+        self.buoyancy = buoyancy;
+    }
+
+    fn ambient(&self) -> f32 {
+        self.ambient
+    }
+
+    fn set_ambient(&mut self, ambient: f32) {
+        self.ambient = ambient;
+    }
+
+    fn cooling(&self) -> f32 {
+        self.cooling
+    }
+
+    fn set_cooling(&mut self, cooling: f32) {
+        self.cooling = cooling;
+    }
+
+    fn decay(&mut self, amount: f32) {
+        for dye in self.dyes.iter_mut() {
+            for d in dye.iter_mut() {
+                *d = (*d - amount).max(0.0).min(100.0);
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        let fields = vec![
+            &mut self.u,
+            &mut self.v,
+            &mut self.u_old,
+            &mut self.v_old,
+            &mut self.u_impulse,
+            &mut self.v_impulse,
+            &mut self.pressure,
+            &mut self.temperature,
+        ];
+        for field in fields.into_iter().chain(self.dyes.iter_mut()) {
+            for x in field.iter_mut() {
+                *x = 0.0;
+            }
+        }
+        self.seed_particles();
+    }
+
+    fn step(&mut self) {
+        self.particles_to_grid();
+        self.enforce_boundaries();
+        self.u_old.copy_from_slice(&self.u);
+        self.v_old.copy_from_slice(&self.v);
+
+        self.apply_forces();
+        self.enforce_boundaries();
+        self.project();
+        self.enforce_boundaries();
+
+        self.grid_to_particles();
+        self.advect_particles();
+        self.reseed_particles();
+
+        let mut dyes = std::mem::take(&mut self.dyes);
+        for dye in dyes.iter_mut() {
+            self.advect_scalar(dye);
+        }
+        self.dyes = dyes;
+
+        let mut temperature = std::mem::take(&mut self.temperature);
+        self.advect_scalar(&mut temperature);
+        let cooling = (self.cooling * self.dt).min(1.0);
+        for t in temperature.iter_mut() {
+            *t += (self.ambient - *t) * cooling;
+        }
+        self.temperature = temperature;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reseeding_keeps_the_particle_count_bounded() {
+        let n = 20;
+        let mut fluid = MacFluid::new(n, 1, 0.1, 3);
+        // Everything pushed towards the centre piles particles up there
+        for _ in 0..200 {
+            for j in 2..n - 2 {
+                for i in 2..n - 2 {
+                    let dx = n as f32 * 0.5 - i as f32;
+                    let dy = n as f32 * 0.5 - j as f32;
+                    fluid.add_velocity(i, j, dx * 0.01, dy * 0.01);
+                }
+            }
+            fluid.step();
+            assert!(fluid.particle_count() <= MAX_PARTICLES_PER_CELL * (n - 2) * (n - 2));
+        }
+    }
+
+    fn max_divergence(fluid: &MacFluid) -> f32 {
+        fluid.divergence().iter().fold(0.0, |max, d| max.max(d.abs()))
+    }
+
+    #[test]
+    fn projection_leaves_no_divergence() {
+        let n = 16;
+        let mut fluid = MacFluid::new(n, 1, 0.1, 5);
+        for j in 6..9 {
+            for i in 5..8 {
+                fluid.set_solid(i, j, true);
+            }
+        }
+        let mut rng = Pcg32::new(6);
+        for u in fluid.u.iter_mut().chain(fluid.v.iter_mut()) {
+            *u = rng.range_f32(-1.0, 1.0);
+        }
+        fluid.enforce_boundaries();
+        let before = max_divergence(&fluid);
+
+        fluid.set_iterations(2000);
+        fluid.project();
+        let after = max_divergence(&fluid);
+        assert!(after < before * 1e-4, "divergence {} became {}", before, after);
+    }
+
+    #[test]
+    fn zero_flip_ratio_is_pure_pic() {
+        let n = 12;
+        let mut fluid = MacFluid::new(n, 1, 0.1, 7);
+        let mut rng = Pcg32::new(8);
+        for u in fluid.u.iter_mut().chain(fluid.v.iter_mut()) {
+            *u = rng.range_f32(-1.0, 1.0);
+        }
+        for u in fluid.u_old.iter_mut().chain(fluid.v_old.iter_mut()) {
+            *u = rng.range_f32(-1.0, 1.0);
+        }
+        // Whatever the particles carried is forgotten
+        for p in fluid.particles.iter_mut() {
+            p.vx = rng.range_f32(-10.0, 10.0);
+            p.vy = rng.range_f32(-10.0, 10.0);
+        }
+        fluid.set_flip_ratio(0.0);
+        fluid.grid_to_particles();
+        for p in fluid.particles.iter() {
+            assert_eq!(p.vx, sample(&fluid.u, n + 1, n, p.x + 0.5, p.y));
+            assert_eq!(p.vy, sample(&fluid.v, n, n + 1, p.x, p.y + 0.5));
+        }
+    }
+}
@@ -0,0 +1,303 @@
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+
+/// Classic 37 entry PSX Doom fire palette, from coldest to hottest.
+pub const DOOM_PALETTE: [[u8; 3]; 37] = [
+    [0x07, 0x07, 0x07], [0x1F, 0x07, 0x07], [0x2F, 0x0F, 0x07], [0x47, 0x0F, 0x07],
+    [0x57, 0x17, 0x07], [0x67, 0x1F, 0x07], [0x77, 0x1F, 0x07], [0x8F, 0x27, 0x07],
+    [0x9F, 0x2F, 0x07], [0xAF, 0x3F, 0x07], [0xBF, 0x47, 0x07], [0xC7, 0x47, 0x07],
+    [0xDF, 0x4F, 0x07], [0xDF, 0x57, 0x07], [0xDF, 0x57, 0x07], [0xD7, 0x5F, 0x07],
+    [0xD7, 0x5F, 0x07], [0xD7, 0x67, 0x0F], [0xCF, 0x6F, 0x0F], [0xCF, 0x77, 0x0F],
+    [0xCF, 0x7F, 0x0F], [0xCF, 0x87, 0x17], [0xC7, 0x87, 0x17], [0xC7, 0x8F, 0x17],
+    [0xC7, 0x97, 0x1F], [0xBF, 0x9F, 0x1F], [0xBF, 0x9F, 0x1F], [0xBF, 0xA7, 0x27],
+    [0xBF, 0xA7, 0x27], [0xBF, 0xAF, 0x2F], [0xB7, 0xAF, 0x2F], [0xB7, 0xB7, 0x2F],
+    [0xB7, 0xB7, 0x37], [0xCF, 0xCF, 0x6F], [0xDF, 0xDF, 0x9F], [0xEF, 0xEF, 0xC7],
+    [0xFF, 0xFF, 0xFF],
+];
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ColorStop {
+    pub position: f32,
+    pub color: [u8; 3],
+}
+
+impl ColorStop {
+    pub fn new(position: f32, color: [u8; 3]) -> Self {
+        ColorStop { position, color }
+    }
+}
+
+/// Maps a normalised heat value in `[0, 1]` to an RGB colour.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Palette {
+    Cosine {
+        frequency: f32,
+        r_phase: f32,
+        g_phase: f32,
+        b_phase: f32,
+    },
+    Gradient {
+        stops: Vec<ColorStop>,
+    },
+    Lookup {
+        colors: Vec<[u8; 3]>,
+    },
+}
+
+impl Palette {
+    pub fn cosine(frequency: f32, r_phase: f32, g_phase: f32, b_phase: f32) -> Self {
+        Palette::Cosine {
+            frequency,
+            r_phase,
+            g_phase,
+            b_phase,
+        }
+    }
+
+    pub fn doom() -> Self {
+        Palette::Lookup {
+            colors: DOOM_PALETTE.to_vec(),
+        }
+    }
+
+    /// Gradient through `stops`, which may be given in any order.
+    pub fn gradient(mut stops: Vec<ColorStop>) -> Self {
+        sort_stops(&mut stops);
+        Palette::Gradient { stops }
+    }
+
+    pub fn fire_gradient() -> Self {
+        Palette::gradient(vec![
+            ColorStop::new(0.0, [0, 0, 0]),
+            ColorStop::new(0.3, [160, 16, 0]),
+            ColorStop::new(0.6, [240, 120, 0]),
+            ColorStop::new(0.85, [255, 220, 60]),
+            ColorStop::new(1.0, [255, 255, 255]),
+        ])
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Palette::Cosine { .. } => "Cosine",
+            Palette::Gradient { .. } => "Gradient",
+            Palette::Lookup { .. } => "Lookup",
+        }
+    }
+
+    pub fn color(&self, t: f32) -> [u8; 3] {
+        let t = t.max(0.0).min(1.0);
+        match self {
+            Palette::Cosine {
+                frequency,
+                r_phase,
+                g_phase,
+                b_phase,
+            } => {
+                let i = t * 2.0 * PI;
+                let channel = |phase: f32| to_u8((i * frequency + phase).sin() * 0.5 + 0.5);
+                [channel(*r_phase), channel(*g_phase), channel(*b_phase)]
+            }
+            Palette::Gradient { stops } => gradient_color(stops, t),
+            Palette::Lookup { colors } => {
+                if colors.is_empty() {
+                    return [0, 0, 0];
+                }
+                let idx = (t * (colors.len() - 1) as f32).round() as usize;
+                colors[idx]
+            }
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let json = fs::read_to_string(path)?;
+        let mut palette = serde_json::from_str(&json)?;
+        if let Palette::Gradient { stops } = &mut palette {
+            sort_stops(stops);
+        }
+        Ok(palette)
+    }
+
+    /// Reads a lookup table with one colour per line, either `#rrggbb` or
+    /// three decimal components. Blank lines and `//` comments are skipped.
+    pub fn load_lookup<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let mut colors = Vec::new();
+        for line in text.lines() {
+            let line = line.split("//").next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            match parse_color(line) {
+                Some(color) => colors.push(color),
+                None => {
+                    let msg = format!("invalid palette entry {:?}", line);
+                    return Err(io::Error::new(ErrorKind::InvalidData, msg));
+                }
+            }
+        }
+        if colors.is_empty() {
+            return Err(io::Error::new(ErrorKind::InvalidData, "empty palette"));
+        }
+        Ok(Palette::Lookup { colors })
+    }
+}
+
+/// Orders gradient stops by position, any NaN position last.
+pub fn sort_stops(stops: &mut [ColorStop]) {
+    stops.sort_by(|a, b| {
+        a.position
+            .partial_cmp(&b.position)
+            .unwrap_or_else(|| a.position.is_nan().cmp(&b.position.is_nan()))
+    });
+}
+
+fn to_u8(v: f32) -> u8 {
+    (v.max(0.0).min(1.0) * 255.0).round() as u8
+}
+
+fn lerp_u8(a: u8, b: u8, t: f32) -> u8 {
+    (a as f32 + (b as f32 - a as f32) * t).round() as u8
+}
+
+fn gradient_color(stops: &[ColorStop], t: f32) -> [u8; 3] {
+    let first = match stops.first() {
+        Some(stop) => stop,
+        None => return [0, 0, 0],
+    };
+    if t <= first.position {
+        return first.color;
+    }
+    for pair in stops.windows(2) {
+        let (a, b) = (&pair[0], &pair[1]);
+        if t <= b.position {
+            let span = b.position - a.position;
+            let f = if span > 0.0 { (t - a.position) / span } else { 1.0 };
+            return [
+                lerp_u8(a.color[0], b.color[0], f),
+                lerp_u8(a.color[1], b.color[1], f),
+                lerp_u8(a.color[2], b.color[2], f),
+            ];
+        }
+    }
+    stops[stops.len() - 1].color
+}
+
+fn parse_color(s: &str) -> Option<[u8; 3]> {
+    if let Some(hex) = s.strip_prefix('#') {
+        if hex.len() != 6 {
+            return None;
+        }
+        let r = u8::from_str_radix(&hex[0..2], 16).ok()?;
+        let g = u8::from_str_radix(&hex[2..4], 16).ok()?;
+        let b = u8::from_str_radix(&hex[4..6], 16).ok()?;
+        return Some([r, g, b]);
+    }
+    let parts: Vec<u8> = s
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|p| !p.is_empty())
+        .map(|p| p.parse().ok())
+        .collect::<Option<_>>()?;
+    match parts.as_slice() {
+        [r, g, b] => Some([*r, *g, *b]),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("doom_fire_{}_{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn colors_parse_as_hex_or_decimal() {
+        assert_eq!(parse_color("#1f07Ff"), Some([0x1F, 0x07, 0xFF]));
+        assert_eq!(parse_color("31 7 255"), Some([31, 7, 255]));
+        assert_eq!(parse_color("31, 7,255"), Some([31, 7, 255]));
+        for bad in ["#1f07f", "#1f07fg", "31 7", "31 7 256", "31 7 -1", "1 2 3 4", ""].iter() {
+            assert_eq!(parse_color(bad), None, "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn lookup_files_skip_comments_and_reject_bad_lines() {
+        let path = temp_path("lookup.txt");
+        fs::write(&path, "// coldest\n#000000\n\n128 0 0 // red\n255,255,255\n").unwrap();
+        let palette = Palette::load_lookup(&path).unwrap();
+        assert_eq!(
+            palette,
+            Palette::Lookup {
+                colors: vec![[0, 0, 0], [128, 0, 0], [255, 255, 255]]
+            }
+        );
+        assert_eq!(palette.color(0.5), [128, 0, 0]);
+
+        fs::write(&path, "#000000\nred\n").unwrap();
+        assert_eq!(Palette::load_lookup(&path).unwrap_err().kind(), ErrorKind::InvalidData);
+        fs::write(&path, "// nothing\n").unwrap();
+        assert_eq!(Palette::load_lookup(&path).unwrap_err().kind(), ErrorKind::InvalidData);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn gradients_interpolate_between_stops() {
+        let stops = [ColorStop::new(0.2, [0, 0, 0]), ColorStop::new(0.6, [200, 100, 40])];
+        assert_eq!(gradient_color(&stops, 0.0), [0, 0, 0]);
+        assert_eq!(gradient_color(&stops, 0.4), [100, 50, 20]);
+        assert_eq!(gradient_color(&stops, 0.5), [150, 75, 30]);
+        assert_eq!(gradient_color(&stops, 1.0), [200, 100, 40]);
+        assert_eq!(gradient_color(&[], 0.5), [0, 0, 0]);
+    }
+
+    #[test]
+    fn stops_are_sorted_with_nan_last() {
+        let palette = Palette::gradient(vec![
+            ColorStop::new(1.0, [255, 255, 255]),
+            ColorStop::new(f32::NAN, [1, 2, 3]),
+            ColorStop::new(0.0, [0, 0, 0]),
+            ColorStop::new(0.5, [100, 0, 0]),
+        ]);
+        match &palette {
+            Palette::Gradient { stops } => {
+                let colors: Vec<[u8; 3]> = stops.iter().map(|stop| stop.color).collect();
+                assert_eq!(colors, vec![[0, 0, 0], [100, 0, 0], [255, 255, 255], [1, 2, 3]]);
+            }
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(palette.color(0.25), [50, 0, 0]);
+    }
+
+    #[test]
+    fn palettes_round_trip_through_json() {
+        let path = temp_path("palette.json");
+        for palette in [Palette::cosine(0.57, 7.0, 1.5, 8.5), Palette::doom(), Palette::fire_gradient()].iter() {
+            palette.save(&path).unwrap();
+            assert_eq!(&Palette::load(&path).unwrap(), palette);
+        }
+
+        // Hand edited files may list the stops in any order
+        let reversed = r#"{"kind": "gradient", "stops": [
+            {"position": 1.0, "color": [255, 255, 255]},
+            {"position": 0.0, "color": [0, 0, 0]}
+        ]}"#;
+        fs::write(&path, reversed).unwrap();
+        let expected = Palette::gradient(vec![
+            ColorStop::new(0.0, [0, 0, 0]),
+            ColorStop::new(1.0, [255, 255, 255]),
+        ]);
+        assert_eq!(Palette::load(&path).unwrap(), expected);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,450 @@
+use crate::rng::Pcg32;
+use nannou::image::{self, imageops::FilterType, ImageResult};
+use std::f32::consts::{PI, TAU};
+use std::path::Path;
+
+/// Weight of the 3x3 mean blur mixed into the food scent every step.
+const SCENT_DIFFUSE: f32 = 1.0;
+/// Fraction of the food scent lost every step, low so that it spreads far.
+const SCENT_EVAPORATION: f32 = 0.002;
+
+/// PCG output permutation used as a stateless hash, wrapping on overflow.
+pub fn hash_u32(s: u32) -> u32 {
+    let state = s.wrapping_mul(747796405).wrapping_add(2891336453);
+    let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277803737);
+    (word >> 22) ^ word
+}
+
+/// Uniform value in `[0, 1]` derived from `s`.
+pub fn hash(s: u32) -> f32 {
+    hash_u32(s) as f32 / u32::MAX as f32
+}
+
+/// Parameters of one species in the Jones slime mould model. Angles are in
+/// radians and distances in trail cells.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SlimeSettings {
+    pub move_speed: f32,
+    pub turn_speed: f32,
+    pub sensor_angle: f32,
+    pub sensor_offset: f32,
+    pub deposit: f32,
+    /// Weight of the 3x3 mean blur mixed into the trail every step.
+    pub diffuse: f32,
+    /// Fraction of the trail lost every step.
+    pub evaporation: f32,
+}
+
+impl Default for SlimeSettings {
+    fn default() -> Self {
+        SlimeSettings {
+            move_speed: 1.0,
+            turn_speed: TAU / 16.0,
+            sensor_angle: TAU / 16.0,
+            sensor_offset: 9.0,
+            deposit: 0.5,
+            diffuse: 0.5,
+            evaporation: 0.05,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Agent {
+    pub x: f32,
+    pub y: f32,
+    pub angle: f32,
+    pub species: usize,
+}
+
+impl Agent {
+    pub fn new(x: f32, y: f32, angle: f32, species: usize) -> Self {
+        Agent { x, y, angle, species }
+    }
+}
+
+/// Where `Physarum::spawn` places new agents.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SpawnMode {
+    /// All at the centre, heading in random directions.
+    Point,
+    /// Anywhere on the grid.
+    Random,
+    /// Inside a centred disc of the given radius in cells.
+    Disc(f32),
+    /// On a centred circle of the given radius, facing the centre.
+    Ring(f32),
+    /// On the cells set in a mask of the grid size.
+    Mask(Vec<bool>),
+}
+
+/// Loads an image scaled to the grid as one brightness in `[0, 1]` per
+/// cell, in the row by row from the bottom layout of the trails.
+pub fn load_brightness(path: &Path, width: usize, height: usize) -> ImageResult<Vec<f32>> {
+    let image = image::open(path)?
+        .resize_exact(width as u32, height as u32, FilterType::Triangle)
+        .to_luma();
+    let mut cells = vec![0.0; width * height];
+    for (x, y, pixel) in image.enumerate_pixels() {
+        cells[x as usize + (height - 1 - y as usize) * width] = pixel[0] as f32 / 255.0;
+    }
+    Ok(cells)
+}
+
+/// Agents that follow, deposit into and share diffusing trail maps. Every
+/// species deposits into its own trail, stored row by row from the bottom
+/// with values saturating at 1, and senses the weighted sum of all trails
+/// given by its row of the interaction matrix. The random turns depend only
+/// on the seed, the step and the agent index, so a run is replayed exactly
+/// from the same seed and spawn. Food cells deposit every step into a scent
+/// map that diffuses and evaporates like the trails and that every species
+/// follows whatever the interaction matrix, so that all of them are drawn to
+/// the food from afar.
+pub struct Physarum {
+    width: usize,
+    height: usize,
+    trails: Vec<Vec<f32>>,
+    scratch: Vec<f32>,
+    agents: Vec<Agent>,
+    species: Vec<SlimeSettings>,
+    interaction: Vec<Vec<f32>>,
+    food: Vec<f32>,
+    scent: Vec<f32>,
+    rng: Pcg32,
+    seed: u64,
+    steps: u32,
+}
+
+impl Physarum {
+    /// Species follow their own trail and avoid the others until the
+    /// interaction matrix is changed.
+    pub fn new(width: usize, height: usize, species: Vec<SlimeSettings>, seed: u64) -> Self {
+        let count = species.len();
+        let interaction = (0..count)
+            .map(|a| (0..count).map(|b| if a == b { 1.0 } else { -1.0 }).collect())
+            .collect();
+        Physarum {
+            width,
+            height,
+            trails: vec![vec![0.0; width * height]; count],
+            scratch: vec![0.0; width * height],
+            agents: Vec::new(),
+            species,
+            interaction,
+            food: vec![0.0; width * height],
+            scent: vec![0.0; width * height],
+            rng: Pcg32::new(seed),
+            seed,
+            steps: 0,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn index(&self, x: usize, y: usize) -> usize {
+        x + y * self.width
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn steps(&self) -> u32 {
+        self.steps
+    }
+
+    pub fn species_count(&self) -> usize {
+        self.species.len()
+    }
+
+    pub fn trail(&self, species: usize) -> &[f32] {
+        &self.trails[species]
+    }
+
+    pub fn agents(&self) -> &[Agent] {
+        &self.agents
+    }
+
+    pub fn add_agent(&mut self, agent: Agent) {
+        assert!(agent.species < self.species.len(), "unknown species {}", agent.species);
+        self.agents.push(agent);
+    }
+
+    pub fn clear_agents(&mut self) {
+        self.agents.clear();
+    }
+
+    /// Adds `count` agents placed by `mode`, species taking turns. The
+    /// positions and headings are drawn from the seeded generator.
+    pub fn spawn(&mut self, mode: &SpawnMode, count: usize) {
+        let (w, h) = (self.width as f32, self.height as f32);
+        let (cx, cy) = (w * 0.5, h * 0.5);
+        let cells: Vec<usize> = match mode {
+            SpawnMode::Mask(mask) => (0..mask.len()).filter(|&i| mask[i]).collect(),
+            _ => Vec::new(),
+        };
+        for i in 0..count {
+            let species = (self.agents.len() + i) % self.species.len();
+            let heading = self.rng.range_f32(0.0, TAU);
+            let (x, y, angle) = match mode {
+                SpawnMode::Point => (cx, cy, heading),
+                SpawnMode::Random => {
+                    let x = self.rng.range_f32(0.0, w - 1.0);
+                    let y = self.rng.range_f32(0.0, h - 1.0);
+                    (x, y, heading)
+                }
+                SpawnMode::Disc(radius) => {
+                    // Square root keeps the density uniform over the area
+                    let r = radius * self.rng.next_f32().sqrt();
+                    (cx + r * heading.cos(), cy + r * heading.sin(), self.rng.range_f32(0.0, TAU))
+                }
+                SpawnMode::Ring(radius) => {
+                    (cx + radius * heading.cos(), cy + radius * heading.sin(), heading + PI)
+                }
+                SpawnMode::Mask(_) => {
+                    if cells.is_empty() {
+                        return;
+                    }
+                    let cell = cells[self.rng.below(cells.len() as u32) as usize];
+                    let x = (cell % self.width) as f32 + self.rng.next_f32();
+                    let y = (cell / self.width) as f32 + self.rng.next_f32();
+                    (x, y, heading)
+                }
+            };
+            let x = x.max(0.0).min(w - 1.0);
+            let y = y.max(0.0).min(h - 1.0);
+            self.agents.push(Agent::new(x, y, angle, species));
+        }
+    }
+
+    pub fn food(&self) -> &[f32] {
+        &self.food
+    }
+
+    /// Scent deposited on the cell every step, zero removes the food.
+    pub fn set_food(&mut self, x: usize, y: usize, amount: f32) {
+        let index = self.index(x, y);
+        self.food[index] = amount;
+    }
+
+    /// Replaces the food with the brightness of an image times `amount`.
+    pub fn load_food(&mut self, path: &Path, amount: f32) -> ImageResult<()> {
+        let brightness = load_brightness(path, self.width, self.height)?;
+        for (food, b) in self.food.iter_mut().zip(brightness) {
+            *food = b * amount;
+        }
+        Ok(())
+    }
+
+    pub fn clear_food(&mut self) {
+        for f in self.food.iter_mut() {
+            *f = 0.0;
+        }
+    }
+
+    pub fn settings(&self, species: usize) -> SlimeSettings {
+        self.species[species]
+    }
+
+    pub fn set_settings(&mut self, species: usize, settings: SlimeSettings) {
+        self.species[species] = settings;
+    }
+
+    /// How strongly `species` is drawn to the trail of `other`, negative
+    /// values repel.
+    pub fn interaction(&self, species: usize, other: usize) -> f32 {
+        self.interaction[species][other]
+    }
+
+    pub fn set_interaction(&mut self, species: usize, other: usize, weight: f32) {
+        self.interaction[species][other] = weight;
+    }
+
+    /// Clears the trails and the food scent, which the food rebuilds.
+    pub fn clear_trail(&mut self) {
+        for trail in self.trails.iter_mut() {
+            for t in trail.iter_mut() {
+                *t = 0.0;
+            }
+        }
+        for s in self.scent.iter_mut() {
+            *s = 0.0;
+        }
+    }
+
+    pub fn step(&mut self) {
+        self.move_agents();
+        self.deposit_food();
+        self.diffuse_and_evaporate();
+        self.steps = self.steps.wrapping_add(1);
+    }
+
+    fn move_agents(&mut self) {
+        let (w, h) = (self.width, self.height);
+        let max_x = w as f32 - 1.0;
+        let max_y = h as f32 - 1.0;
+        let count = self.agents.len() as u32;
+        let seed = hash_u32(self.seed as u32 ^ hash_u32((self.seed >> 32) as u32));
+        for (i, agent) in self.agents.iter_mut().enumerate() {
+            let rand = hash(seed ^ hash_u32(self.steps.wrapping_mul(count).wrapping_add(i as u32)));
+            let s = self.species[agent.species];
+            let weights = &self.interaction[agent.species];
+            let (trails, scent) = (&self.trails, &self.scent);
+
+            let probe = |offset_angle: f32| {
+                let angle = agent.angle + offset_angle;
+                let x = agent.x + angle.cos() * s.sensor_offset;
+                let y = agent.y + angle.sin() * s.sensor_offset;
+                sense(trails, scent, weights, w, h, x, y)
+            };
+            let forward = probe(0.0);
+            let left = probe(s.sensor_angle);
+            let right = probe(-s.sensor_angle);
+            if forward > left && forward > right {
+                // Keep heading
+            } else if forward < left && forward < right {
+                agent.angle += if rand < 0.5 { s.turn_speed } else { -s.turn_speed };
+            } else if left > right {
+                agent.angle += s.turn_speed;
+            } else if right > left {
+                agent.angle -= s.turn_speed;
+            }
+
+            let x = agent.x + agent.angle.cos() * s.move_speed;
+            let y = agent.y + agent.angle.sin() * s.move_speed;
+            if x < 0.0 || y < 0.0 || x > max_x || y > max_y {
+                agent.x = x.max(0.0).min(max_x);
+                agent.y = y.max(0.0).min(max_y);
+                agent.angle = rand * TAU;
+            } else {
+                agent.x = x;
+                agent.y = y;
+            }
+
+            let index = agent.x as usize + agent.y as usize * w;
+            let trail = &mut self.trails[agent.species];
+            trail[index] = (trail[index] + s.deposit).min(1.0);
+        }
+    }
+
+    fn deposit_food(&mut self) {
+        for (s, &food) in self.scent.iter_mut().zip(self.food.iter()) {
+            if food > 0.0 {
+                *s = (*s + food).min(1.0);
+            }
+        }
+    }
+
+    fn diffuse_and_evaporate(&mut self) {
+        for species in 0..self.species.len() {
+            let s = self.species[species];
+            let keep = 1.0 - s.evaporation;
+            blur(&self.trails[species], &mut self.scratch, self.width, self.height, s.diffuse, keep);
+            std::mem::swap(&mut self.trails[species], &mut self.scratch);
+        }
+        let keep = 1.0 - SCENT_EVAPORATION;
+        blur(&self.scent, &mut self.scratch, self.width, self.height, SCENT_DIFFUSE, keep);
+        std::mem::swap(&mut self.scent, &mut self.scratch);
+    }
+}
+
+fn blur(trail: &[f32], out: &mut [f32], w: usize, h: usize, diffuse: f32, keep: f32) {
+    for y in 0..h {
+        for x in 0..w {
+            let mut sum = 0.0;
+            let mut cells = 0.0;
+            for ny in y.saturating_sub(1)..(y + 2).min(h) {
+                for nx in x.saturating_sub(1)..(x + 2).min(w) {
+                    sum += trail[nx + ny * w];
+                    cells += 1.0;
+                }
+            }
+            let index = x + y * w;
+            let value = trail[index];
+            let blurred = value + (sum / cells - value) * diffuse;
+            out[index] = (blurred * keep).max(0.0);
+        }
+    }
+}
+
+/// Trails weighted by the interaction row plus the food scent at `(x, y)`.
+fn sense(
+    trails: &[Vec<f32>],
+    scent: &[f32],
+    weights: &[f32],
+    w: usize,
+    h: usize,
+    x: f32,
+    y: f32,
+) -> f32 {
+    if x < 0.0 || y < 0.0 || x >= w as f32 || y >= h as f32 {
+        return 0.0;
+    }
+    let index = x as usize + y as usize * w;
+    let trail: f32 = trails.iter().zip(weights.iter()).map(|(trail, weight)| trail[index] * weight).sum();
+    trail + scent[index]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(seed: u64) -> Vec<Agent> {
+        let species = vec![SlimeSettings::default(); 2];
+        let mut physarum = Physarum::new(32, 32, species, seed);
+        physarum.spawn(&SpawnMode::Random, 100);
+        for _ in 0..1000 {
+            physarum.step();
+        }
+        physarum.agents().to_vec()
+    }
+
+    /// Agents of three species leaving no trail, spread over the grid with
+    /// food at its centre, and how many end up within `radius` of it.
+    fn gathered_at_food(food: f32, radius: f32) -> usize {
+        let settings = SlimeSettings {
+            deposit: 0.0,
+            ..SlimeSettings::default()
+        };
+        let mut physarum = Physarum::new(48, 48, vec![settings; 3], 1);
+        let (fx, fy) = (24.0, 24.0);
+        for y in 22..=26 {
+            for x in 22..=26 {
+                physarum.set_food(x, y, food);
+            }
+        }
+        // Scent spreads before the agents arrive
+        for _ in 0..300 {
+            physarum.step();
+        }
+        physarum.spawn(&SpawnMode::Random, 60);
+        for _ in 0..200 {
+            physarum.step();
+        }
+        physarum
+            .agents()
+            .iter()
+            .filter(|a| (a.x - fx).hypot(a.y - fy) < radius)
+            .count()
+    }
+
+    #[test]
+    fn every_species_is_drawn_to_food_from_afar() {
+        // Default interactions, each species repelled by the other two
+        let with = gathered_at_food(0.5, 8.0);
+        let without = gathered_at_food(0.0, 8.0);
+        assert!(with >= 45 && without < 15, "{} agents near the food, {} without it", with, without);
+    }
+
+    #[test]
+    fn runs_replay_from_the_seed() {
+        assert_eq!(run(42), run(42));
+        assert_ne!(run(42), run(43));
+    }
+}
@@ -0,0 +1,137 @@
+use nannou::prelude::*;
+use nannou::wgpu;
+
+/// CPU side RGBA8 image of a simulation grid. Cells are addressed with
+/// `y = 0` at the bottom like the grids themselves, rows are stored top down
+/// so the bytes can be uploaded to a texture as they are.
+pub struct PixelBuffer {
+    width: usize,
+    height: usize,
+    data: Vec<u8>,
+}
+
+impl PixelBuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        PixelBuffer {
+            width,
+            height,
+            data: vec![0; width * height * 4],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+        self.data = vec![0; width * height * 4];
+    }
+
+    fn offset(&self, x: usize, y: usize) -> usize {
+        ((self.height - 1 - y) * self.width + x) * 4
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, rgba: [u8; 4]) {
+        let offset = self.offset(x, y);
+        self.data[offset..offset + 4].copy_from_slice(&rgba);
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> [u8; 4] {
+        let offset = self.offset(x, y);
+        let mut rgba = [0; 4];
+        rgba.copy_from_slice(&self.data[offset..offset + 4]);
+        rgba
+    }
+
+    pub fn fill(&mut self, rgba: [u8; 4]) {
+        for pixel in self.data.chunks_exact_mut(4) {
+            pixel.copy_from_slice(&rgba);
+        }
+    }
+
+    /// Writes every cell from a grid stored row by row from the bottom,
+    /// the layout used by the fire, fluid and ant grids.
+    pub fn fill_from<T, F>(&mut self, cells: &[T], to_rgba: F)
+    where
+        F: Fn(&T) -> [u8; 4],
+    {
+        assert_eq!(cells.len(), self.width * self.height);
+        for (y, row) in cells.chunks_exact(self.width).enumerate() {
+            for (x, cell) in row.iter().enumerate() {
+                self.set(x, y, to_rgba(cell));
+            }
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+/// GPU texture a `PixelBuffer` is uploaded to once per frame.
+pub struct PixelTexture {
+    texture: wgpu::Texture,
+}
+
+impl PixelTexture {
+    pub fn new(window: &Window, width: usize, height: usize) -> Self {
+        let texture = wgpu::TextureBuilder::new()
+            .size([width as u32, height as u32])
+            .format(wgpu::TextureFormat::Rgba8UnormSrgb)
+            .usage(wgpu::TextureUsage::COPY_DST | wgpu::TextureUsage::SAMPLED)
+            .build(window.swap_chain_device());
+        PixelTexture { texture }
+    }
+
+    pub fn matches(&self, pixels: &PixelBuffer) -> bool {
+        let [w, h] = self.texture.size();
+        w as usize == pixels.width() && h as usize == pixels.height()
+    }
+
+    pub fn upload(&self, app: &App, frame: &Frame, pixels: &PixelBuffer) {
+        let window = app.window(frame.window_id()).unwrap();
+        let device = window.swap_chain_device();
+        let mut encoder = frame.command_encoder();
+        self.texture.upload_data(device, &mut *encoder, pixels.as_bytes());
+    }
+
+    /// Draws the texture stretched over `rect` without smoothing the cells.
+    pub fn draw(&self, draw: &Draw, rect: Rect) {
+        let sampler = wgpu::SamplerBuilder::new()
+            .min_filter(wgpu::FilterMode::Nearest)
+            .mag_filter(wgpu::FilterMode::Nearest)
+            .into_descriptor();
+        draw.sampler(sampler)
+            .texture(&self.texture)
+            .xy(rect.xy())
+            .wh(rect.wh());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fill_from_writes_rows_top_down() {
+        let mut pixels = PixelBuffer::new(3, 2);
+        let palette = [[0, 0, 0, 255], [255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 128]];
+        // Bottom row first, as the grids store it
+        let cells = [0usize, 1, 2, 3, 2, 1];
+        pixels.fill_from(&cells, |&c| palette[c]);
+
+        let expected: Vec<u8> = [3, 2, 1, 0, 1, 2]
+            .iter()
+            .flat_map(|&c| palette[c].to_vec())
+            .collect();
+        assert_eq!(pixels.as_bytes(), &expected[..]);
+        assert_eq!(pixels.get(2, 0), palette[2]);
+        assert_eq!(pixels.get(0, 1), palette[3]);
+    }
+}
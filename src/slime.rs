@@ -1,4 +1,9 @@
 use nannou::prelude::*;
+use nannou::ui::prelude::*;
+use doom_fire::args::{exit_with_usage, or_exit, Args};
+use doom_fire::physarum::{load_brightness, Physarum, SlimeSettings, SpawnMode};
+use doom_fire::pixels::{PixelBuffer, PixelTexture};
+use std::path::Path;
 
 fn main() {
     nannou::app(model)
@@ -6,95 +11,299 @@ fn main() {
         .run();
 }
 
-const HASH_F: u32 = 2654435769;
-const SIZE: u32 = 400;
+const SIZE: usize = 400;
 const SIZE_F: f32 = SIZE as f32;
 const SCL: f32 = 2.0;
-const U_SIZE: u32 = SIZE * SCL as u32;
-const H_SCL: f32 = SCL * 0.5;
-const OFFSET: f32 = SIZE as f32 * H_SCL;
-const MOVE_SPEED: f32 = 1.0;
-const DT: f32 = 0.1;
-
-
-fn hash(s: u32) -> f32 {
-    let mut state = s;
-    state ^= 2747636419;
-    state *= HASH_F;
-    state ^= state >> 16;
-    state *= HASH_F;
-    state ^= state >> 16;
-    state *= HASH_F;
-    state as f32 / u32::MAX as f32
-}
-
-struct Model {
-    agents: Vec<Agent>
-}
+const U_SIZE: u32 = SIZE as u32 * SCL as u32;
+const AGENTS: usize = 10000;
+const FOOD: f32 = 0.5;
+const FOOD_RADIUS: i32 = 3;
+const FOOD_COLOR: [u8; 4] = [255, 220, 120, 255];
+// Trail colour of each species
+const SPECIES_COLORS: [[f32; 3]; 3] = [[1.0, 0.2, 0.2], [0.2, 1.0, 0.3], [0.3, 0.4, 1.0]];
+const USAGE: &str = "Usage: slime [seed] [--food <image>] [--mask <image>]";
 
-struct Agent {
-    position: Vector2<f32>,
-    angle: f32,
-}
-impl Agent {
-    pub fn new(position: Vector2<f32>, angle: f32) -> Self {
-        Agent {
-            position,
-            angle,
-        }
+widget_ids! {
+    struct Ids {
+        title,
+        move_speed,
+        turn_speed,
+        sensor_angle,
+        sensor_offset,
+        deposit,
+        diffuse,
+        evaporation,
+        interactions[],
     }
+}
 
-    fn update(&mut self) {
-        let rand = hash(self.position.y as u32 * SIZE + self.position.x as u32);
-        let a = self.angle * TAU;
-        let direction = vec2(a.cos(), a.sin());
-        let mut new_position = self.position + direction * MOVE_SPEED * DT;
-        if new_position.x > SIZE_F - 1.0 || new_position.x < 0.0 || new_position.y > SIZE_F - 1.0 || new_position.y < 0.0 {
-            new_position.x = clamp(new_position.x, 0.0, SIZE_F);
-            new_position.y = clamp(new_position.y, 0.0, SIZE_F);
-            self.angle = rand;
-        }
-        self.position = new_position;
-    }
+struct Model {
+    ui: Ui,
+    ids: Ids,
+    physarum: Physarum,
+    selected: usize,
+    mask: Option<Vec<bool>>,
+    pixels: PixelBuffer,
+    texture: PixelTexture,
 }
 
 fn model(app: &App) -> Model {
-    let _window = app
+    let window = app
         .new_window()
         .size(U_SIZE, U_SIZE)
         .view(view)
+        .key_pressed(key_pressed)
+        .mouse_pressed(mouse_pressed)
         .build()
         .unwrap();
-    let mut agents: Vec<Agent> = Vec::new();
-    for _i in 0..100 {
-        //let x = SIZE_F * random::<f32>();
-        //let y = SIZE_F * random::<f32>();
-        let x = SIZE_F * 0.5;
-        let y = SIZE_F * 0.5;
-        agents.push(Agent::new(vec2(x, y), random::<f32>()));
+
+    let ui_window = app.new_window()
+        .title(app.exe_name().unwrap() + " controls")
+        .size(300, 360)
+        .view(ui_view)
+        .event(ui_event)
+        .key_pressed(key_pressed)
+        .build()
+        .unwrap();
+
+    let mut ui = app.new_ui().window(ui_window).build().unwrap();
+    let ids = Ids::new(ui.widget_id_generator());
+
+    ui.clear_with(nannou::ui::prelude::color::DARK_CHARCOAL);
+
+    // Runs are replayed by passing the printed seed back as an argument
+    let mut seed = None;
+    let mut food_path = None;
+    let mut mask_path = None;
+    let mut args = Args::from_env();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--food" => food_path = Some(or_exit(args.value(&arg), USAGE)),
+            "--mask" => mask_path = Some(or_exit(args.value(&arg), USAGE)),
+            other => match other.parse::<u64>() {
+                Ok(n) if seed.is_none() => seed = Some(n),
+                _ => exit_with_usage(&format!("Unknown argument {:?}", other), USAGE),
+            },
+        }
     }
-    Model {
-        agents
+    let seed = seed.unwrap_or_else(random::<u64>);
+    println!("Seed {}", seed);
+    let species = vec![
+        SlimeSettings::default(),
+        SlimeSettings {
+            sensor_angle: TAU / 8.0,
+            sensor_offset: 15.0,
+            ..SlimeSettings::default()
+        },
+        SlimeSettings {
+            move_speed: 1.5,
+            turn_speed: TAU / 10.0,
+            evaporation: 0.1,
+            ..SlimeSettings::default()
+        },
+    ];
+    let mut physarum = Physarum::new(SIZE, SIZE, species, seed);
+    if let Some(path) = food_path {
+        if let Err(error) = physarum.load_food(Path::new(&path), FOOD) {
+            eprintln!("Could not load food {:?}: {}", path, error);
+        }
     }
+    let mask = mask_path.and_then(|path| match load_brightness(Path::new(&path), SIZE, SIZE) {
+        Ok(cells) => Some(cells.iter().map(|&b| b > 0.5).collect()),
+        Err(error) => {
+            eprintln!("Could not load spawn mask {:?}: {}", path, error);
+            None
+        }
+    });
+    let spawn_mode = match &mask {
+        Some(mask) => SpawnMode::Mask(mask.clone()),
+        None => SpawnMode::Point,
+    };
+    physarum.spawn(&spawn_mode, AGENTS);
+
+    let mut the_model = Model {
+        ui,
+        ids,
+        physarum,
+        selected: 0,
+        mask,
+        pixels: PixelBuffer::new(SIZE, SIZE),
+        texture: PixelTexture::new(&app.window(window).unwrap(), SIZE, SIZE),
+    };
+
+    // Send a fake ui_event to draw widgets
+    ui_event(&app, &mut the_model, WindowEvent::Focused);
+
+    the_model
 }
 
-fn update(_app: &App, model: &mut Model, _update: Update) {
-    for agent in model.agents.iter_mut() {
-        agent.update();
+fn mix_trails(physarum: &Physarum, index: usize) -> [u8; 4] {
+    let mut rgb = [0.0; 3];
+    for (species, color) in SPECIES_COLORS.iter().enumerate() {
+        let amount = physarum.trail(species)[index].sqrt();
+        for c in 0..3 {
+            rgb[c] += color[c] * amount;
+        }
     }
+    let to_u8 = |v: f32| (clamp(v, 0.0, 1.0) * 255.0) as u8;
+    [to_u8(rgb[0]), to_u8(rgb[1]), to_u8(rgb[2]), 255]
 }
 
+fn key_pressed(app: &App, model: &mut Model, key: Key) {
+    match key {
+        Key::Tab => {
+            model.selected = (model.selected + 1) % model.physarum.species_count();
+            ui_event(app, model, WindowEvent::Focused);
+        }
+        Key::C => {
+            model.physarum.clear_trail();
+        }
+        Key::F => {
+            model.physarum.clear_food();
+        }
+        Key::Key1 => respawn(model, SpawnMode::Point),
+        Key::Key2 => respawn(model, SpawnMode::Random),
+        Key::Key3 => respawn(model, SpawnMode::Disc(SIZE_F * 0.25)),
+        Key::Key4 => respawn(model, SpawnMode::Ring(SIZE_F * 0.4)),
+        Key::Key5 => {
+            if let Some(mask) = model.mask.clone() {
+                respawn(model, SpawnMode::Mask(mask));
+            }
+        }
+        _other_key => {}
+    }
+}
 
+fn respawn(model: &mut Model, mode: SpawnMode) {
+    model.physarum.clear_agents();
+    model.physarum.clear_trail();
+    model.physarum.spawn(&mode, AGENTS);
+}
+
+fn mouse_pressed(app: &App, model: &mut Model, button: MouseButton) {
+    // Left click drops food, right click removes it
+    let amount = if button == MouseButton::Left { FOOD } else { 0.0 };
+    let cx = ((app.mouse.x + U_SIZE as f32 * 0.5) / SCL) as i32;
+    let cy = ((app.mouse.y + U_SIZE as f32 * 0.5) / SCL) as i32;
+    for dy in -FOOD_RADIUS..=FOOD_RADIUS {
+        for dx in -FOOD_RADIUS..=FOOD_RADIUS {
+            let (x, y) = (cx + dx, cy + dy);
+            let inside = x >= 0 && y >= 0 && x < SIZE as i32 && y < SIZE as i32;
+            if inside && dx * dx + dy * dy <= FOOD_RADIUS * FOOD_RADIUS {
+                model.physarum.set_food(x as usize, y as usize, amount);
+            }
+        }
+    }
+}
+
+fn update(_app: &App, model: &mut Model, _update: Update) {
+    model.physarum.step();
+    let food = model.physarum.food();
+    for index in 0..SIZE * SIZE {
+        let rgba = if food[index] > 0.0 { FOOD_COLOR } else { mix_trails(&model.physarum, index) };
+        model.pixels.set(index % SIZE, index / SIZE, rgba);
+    }
+}
 
 fn view(app: &App, model: &Model, frame: Frame){
     let draw = app.draw();
-    if app.elapsed_frames() == 1 {
-        draw.background().color(BLACK);
+    draw.background().color(BLACK);
+    model.texture.upload(app, &frame, &model.pixels);
+    model.texture.draw(&draw, Rect::from_w_h(U_SIZE as f32, U_SIZE as f32));
+    draw.to_frame(app, &frame).unwrap()
+}
+
+fn ui_event(_app: &App, model: &mut Model, _event: WindowEvent) {
+    let ui = &mut model.ui.set_widgets();
+    let ids = &mut model.ids;
+    let physarum = &mut model.physarum;
+    let selected = model.selected;
+    let mut settings = physarum.settings(selected);
+
+    fn slider(val: f32, min: f32, max: f32) -> widget::Slider<'static, f32> {
+        widget::Slider::new(val, min, max)
+            .w_h(260.0, 25.0)
+            .label_font_size(13)
+            .rgb(0.3, 0.3, 0.3)
+            .label_rgb(1.0, 1.0, 1.0)
+            .border(0.0)
     }
-    draw.rect().x_y(0.0, 0.0).w(U_SIZE as f32).h(U_SIZE as f32).color(srgba(0.0, 0.0, 0.0, 0.01));
-    for agent in model.agents.iter() {
-        let pos = agent.position;
-        draw.rect().x_y(pos.x * SCL - OFFSET + H_SCL, pos.y * SCL - OFFSET + H_SCL).w_h(SCL, SCL).color(WHITE);
+
+    widget::Text::new(&format!("Species {} (Tab to switch)", selected))
+        .top_left_with_margins(10.0, 20.0)
+        .w_h(260.0, 20.0)
+        .font_size(15)
+        .rgb(1.0, 1.0, 1.0)
+        .set(ids.title, ui);
+
+    for value in slider(settings.move_speed, 0.1, 3.0)
+        .top_left_with_margins(40.0, 20.0)
+        .label(&format!("Move speed: {:.2}", settings.move_speed))
+        .set(ids.move_speed, ui)
+    {
+        settings.move_speed = value;
     }
-    draw.to_frame(app, &frame).unwrap()
-}
\ No newline at end of file
+    for value in slider(settings.turn_speed, 0.0, PI / 2.0)
+        .top_left_with_margins(70.0, 20.0)
+        .label(&format!("Turn speed: {:.2}", settings.turn_speed))
+        .set(ids.turn_speed, ui)
+    {
+        settings.turn_speed = value;
+    }
+    for value in slider(settings.sensor_angle, 0.0, PI / 2.0)
+        .top_left_with_margins(100.0, 20.0)
+        .label(&format!("Sensor angle: {:.2}", settings.sensor_angle))
+        .set(ids.sensor_angle, ui)
+    {
+        settings.sensor_angle = value;
+    }
+    for value in slider(settings.sensor_offset, 1.0, 30.0)
+        .top_left_with_margins(130.0, 20.0)
+        .label(&format!("Sensor offset: {:.1}", settings.sensor_offset))
+        .set(ids.sensor_offset, ui)
+    {
+        settings.sensor_offset = value;
+    }
+    for value in slider(settings.deposit, 0.0, 1.0)
+        .top_left_with_margins(160.0, 20.0)
+        .label(&format!("Deposit: {:.2}", settings.deposit))
+        .set(ids.deposit, ui)
+    {
+        settings.deposit = value;
+    }
+    for value in slider(settings.diffuse, 0.0, 1.0)
+        .top_left_with_margins(190.0, 20.0)
+        .label(&format!("Diffuse: {:.2}", settings.diffuse))
+        .set(ids.diffuse, ui)
+    {
+        settings.diffuse = value;
+    }
+    for value in slider(settings.evaporation, 0.0, 0.2)
+        .top_left_with_margins(220.0, 20.0)
+        .label(&format!("Evaporation: {:.3}", settings.evaporation))
+        .set(ids.evaporation, ui)
+    {
+        settings.evaporation = value;
+    }
+    physarum.set_settings(selected, settings);
+
+    // Row of the interaction matrix for the selected species
+    let count = physarum.species_count();
+    if ids.interactions.len() < count {
+        ids.interactions.resize(count, &mut ui.widget_id_generator());
+    }
+    for other in 0..count {
+        let weight = physarum.interaction(selected, other);
+        for value in slider(weight, -1.0, 1.0)
+            .top_left_with_margins(260.0 + 30.0 * other as f32, 20.0)
+            .label(&format!("Towards species {}: {:.2}", other, weight))
+            .set(ids.interactions[other], ui)
+        {
+            physarum.set_interaction(selected, other, value);
+        }
+    }
+}
+
+fn ui_view(app: &App, model: &Model, frame: Frame) {
+    model.ui.draw_to_frame_if_changed(app, &frame).unwrap();
+}
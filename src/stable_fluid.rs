@@ -0,0 +1,924 @@
+use crate::fluid_solver::{Boundary, FluidSolver};
+use rayon::prelude::*;
+
+/// Jos Stam's "Real-Time Fluid Dynamics for Games" solver on a square
+/// `size` x `size` grid, the outer ring of cells being the walls. Any number
+/// of dye channels are carried by the same velocity field.
+pub struct Fluid {
+    size: usize,
+    iter: usize,
+    dt: f32,
+    diff: f32,
+    visc: f32,
+    s: Vec<f32>,
+    dyes: Vec<Vec<f32>>,
+    vx: Vec<f32>,
+    vy: Vec<f32>,
+    vx0: Vec<f32>,
+    vy0: Vec<f32>,
+    pressure: Vec<f32>,
+    temperature: Vec<f32>,
+    solid: Vec<bool>,
+    /// Indices of the solid cells, so that boundaries skip the fluid ones.
+    solid_cells: Vec<usize>,
+    boundary: Boundary,
+    vorticity: f32,
+    buoyancy: f32,
+    ambient: f32,
+    cooling: f32,
+    parallel: bool,
+}
+
+#[derive(Clone, Copy)]
+struct Grid<'a> {
+    n: usize,
+    iter: usize,
+    solid: &'a [bool],
+    solid_cells: &'a [usize],
+    boundary: Boundary,
+    parallel: bool,
+}
+
+impl Fluid {
+    pub fn new(size: usize, channels: usize, dt: f32, diff: f32, visc: f32) -> Self {
+        assert!(size >= 3, "fluid grid needs at least one interior cell");
+        let nn = size * size;
+        Fluid {
+            size,
+            iter: 4,
+            dt,
+            diff,
+            visc,
+            s: vec![0.0; nn],
+            dyes: vec![vec![0.0; nn]; channels],
+            vx: vec![0.0; nn],
+            vy: vec![0.0; nn],
+            vx0: vec![0.0; nn],
+            vy0: vec![0.0; nn],
+            pressure: vec![0.0; nn],
+            temperature: vec![0.0; nn],
+            solid: vec![false; nn],
+            solid_cells: Vec::new(),
+            boundary: Boundary::NoSlip,
+            vorticity: 0.0,
+            buoyancy: 0.0,
+            ambient: 0.0,
+            cooling: 0.0,
+            parallel: false,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn set_iterations(&mut self, iter: usize) {
+        self.iter = iter;
+    }
+
+    pub fn parallel(&self) -> bool {
+        self.parallel
+    }
+
+    /// Switches the solver to Jacobi iterations and row-parallel loops on the
+    /// rayon thread pool. Jacobi converges slower than the serial Gauss-Seidel
+    /// sweep, so more iterations may be needed for the same accuracy.
+    pub fn set_parallel(&mut self, parallel: bool) {
+        self.parallel = parallel;
+    }
+
+    pub fn index(&self, x: usize, y: usize) -> usize {
+        index(self.size, x, y)
+    }
+
+    pub fn channels(&self) -> usize {
+        self.dyes.len()
+    }
+
+    pub fn dye(&self, channel: usize) -> &[f32] {
+        &self.dyes[channel]
+    }
+
+    pub fn velocity_x(&self) -> &[f32] {
+        &self.vx
+    }
+
+    pub fn velocity_y(&self) -> &[f32] {
+        &self.vy
+    }
+
+    pub fn vorticity(&self) -> f32 {
+        self.vorticity
+    }
+
+    /// Strength of the vorticity confinement force, zero disables it.
+    pub fn set_vorticity(&mut self, vorticity: f32) {
+        self.vorticity = vorticity;
+    }
+
+    pub fn buoyancy(&self) -> f32 {
+        self.buoyancy
+    }
+
+    /// Upward force per degree above the ambient temperature, zero disables it.
+    pub fn set_buoyancy(&mut self, buoyancy: f32) {
+        self.buoyancy = buoyancy;
+    }
+
+    pub fn ambient(&self) -> f32 {
+        self.ambient
+    }
+
+    pub fn set_ambient(&mut self, ambient: f32) {
+        self.ambient = ambient;
+    }
+
+    pub fn cooling(&self) -> f32 {
+        self.cooling
+    }
+
+    /// Fraction of the difference to the ambient temperature lost per unit of time.
+    pub fn set_cooling(&mut self, cooling: f32) {
+        self.cooling = cooling;
+    }
+
+    pub fn temperature(&self) -> &[f32] {
+        &self.temperature
+    }
+
+    pub fn add_temperature(&mut self, x: usize, y: usize, amount: f32) {
+        let index = self.index(x, y);
+        if self.solid[index] {
+            return;
+        }
+        self.temperature[index] += amount;
+    }
+
+    pub fn boundary(&self) -> Boundary {
+        self.boundary
+    }
+
+    pub fn set_boundary(&mut self, boundary: Boundary) {
+        self.boundary = boundary;
+    }
+
+    pub fn solids(&self) -> &[bool] {
+        &self.solid
+    }
+
+    pub fn is_solid(&self, x: usize, y: usize) -> bool {
+        self.solid[self.index(x, y)]
+    }
+
+    /// Marks an interior cell as solid or fluid. The outer walls are always
+    /// handled by the box boundary and are left untouched.
+    pub fn set_solid(&mut self, x: usize, y: usize, solid: bool) {
+        let n = self.size;
+        if x == 0 || y == 0 || x >= n - 1 || y >= n - 1 {
+            return;
+        }
+        let index = self.index(x, y);
+        if self.solid[index] != solid {
+            if solid {
+                self.solid_cells.push(index);
+            } else {
+                self.solid_cells.retain(|&cell| cell != index);
+            }
+        }
+        self.solid[index] = solid;
+        if solid {
+            for dye in self.dyes.iter_mut() {
+                dye[index] = 0.0;
+            }
+            self.vx[index] = 0.0;
+            self.vy[index] = 0.0;
+        }
+    }
+
+    pub fn clear_solids(&mut self) {
+        for s in self.solid.iter_mut() {
+            *s = false;
+        }
+        self.solid_cells.clear();
+    }
+
+    pub fn add_dye(&mut self, x: usize, y: usize, channel: usize, amount: f32) {
+        let index = self.index(x, y);
+        if self.solid[index] {
+            return;
+        }
+        self.dyes[channel][index] += amount;
+    }
+
+    pub fn add_velocity(&mut self, x: usize, y: usize, x_amount: f32, y_amount: f32) {
+        let index = self.index(x, y);
+        if self.solid[index] {
+            return;
+        }
+        self.vx[index] += x_amount;
+        self.vy[index] += y_amount;
+    }
+
+    pub fn step(&mut self) {
+        if self.buoyancy != 0.0 {
+            self.apply_buoyancy();
+        }
+        if self.vorticity != 0.0 {
+            self.confine_vorticity();
+        }
+
+        let g = Grid {
+            n: self.size,
+            iter: self.iter,
+            solid: &self.solid,
+            solid_cells: &self.solid_cells,
+            boundary: self.boundary,
+            parallel: self.parallel,
+        };
+        let dt = self.dt;
+        let visc = self.visc;
+        let diff = self.diff;
+        let vx = &mut self.vx;
+        let vx0 = &mut self.vx0;
+        let vy = &mut self.vy;
+        let vy0 = &mut self.vy0;
+        let s = &mut self.s;
+        let pressure = &mut self.pressure;
+
+        diffuse(g, 1, vx0, vx, visc, dt);
+        diffuse(g, 2, vy0, vy, visc, dt);
+
+        project(g, vx0, vy0, vx, vy);
+
+        advect(g, 1, vx, vx0, vx0, vy0, dt);
+        advect(g, 2, vy, vy0, vx0, vy0, dt);
+
+        project(g, vx, vy, vx0, vy0);
+        pressure.copy_from_slice(vx0);
+
+        for dye in self.dyes.iter_mut() {
+            diffuse(g, 0, s, dye, diff, dt);
+            advect(g, 0, dye, s, vx, vy, dt);
+        }
+
+        let temperature = &mut self.temperature;
+        diffuse(g, 0, s, temperature, diff, dt);
+        advect(g, 0, temperature, s, vx, vy, dt);
+        let cooling = (self.cooling * dt).min(1.0);
+        for t in temperature.iter_mut() {
+            *t += (self.ambient - *t) * cooling;
+        }
+    }
+
+    fn apply_buoyancy(&mut self) {
+        let force = self.dt * self.buoyancy;
+        for (index, t) in self.temperature.iter().enumerate() {
+            if !self.solid[index] {
+                self.vy[index] += force * (t - self.ambient);
+            }
+        }
+    }
+
+    fn confine_vorticity(&mut self) {
+        let n = self.size;
+        let curl = self.curl();
+        let force = self.dt * self.vorticity;
+        for j in 2..n - 2 {
+            for i in 2..n - 2 {
+                let index = index(n, i, j);
+                if self.solid[index] {
+                    continue;
+                }
+                let grad_x = 0.5 * (curl[index + 1].abs() - curl[index - 1].abs());
+                let grad_y = 0.5 * (curl[index + n].abs() - curl[index - n].abs());
+                let len = (grad_x * grad_x + grad_y * grad_y).sqrt() + 1e-5;
+                let w = curl[index];
+                self.vx[index] += force * (grad_y / len) * w;
+                self.vy[index] -= force * (grad_x / len) * w;
+            }
+        }
+    }
+
+    /// Removes `amount` of every dye from every cell, keeping them in `0..=100`.
+    pub fn decay(&mut self, amount: f32) {
+        for dye in self.dyes.iter_mut() {
+            for d in dye.iter_mut() {
+                *d = (*d - amount).max(0.0).min(100.0);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        let fields = vec![
+            &mut self.s,
+            &mut self.vx,
+            &mut self.vy,
+            &mut self.vx0,
+            &mut self.vy0,
+            &mut self.pressure,
+            &mut self.temperature,
+        ];
+        for field in fields.into_iter().chain(self.dyes.iter_mut()) {
+            for v in field.iter_mut() {
+                *v = 0.0;
+            }
+        }
+    }
+
+    /// Bilinearly interpolated velocity at a point in cell coordinates.
+    pub fn velocity_at(&self, x: f32, y: f32) -> (f32, f32) {
+        let n = self.size;
+        let max = (n - 1) as f32;
+        let x = x.max(0.0).min(max);
+        let y = y.max(0.0).min(max);
+        let i0 = (x.floor() as usize).min(n - 2);
+        let j0 = (y.floor() as usize).min(n - 2);
+        let s1 = x - i0 as f32;
+        let t1 = y - j0 as f32;
+        let sample = |f: &[f32]| {
+            (1.0 - s1) * ((1.0 - t1) * f[i0 + j0 * n] + t1 * f[i0 + (j0 + 1) * n])
+                + s1 * ((1.0 - t1) * f[i0 + 1 + j0 * n] + t1 * f[i0 + 1 + (j0 + 1) * n])
+        };
+        (sample(&self.vx), sample(&self.vy))
+    }
+
+    /// Pressure solved by the last projection of `step`.
+    pub fn pressure(&self) -> &[f32] {
+        &self.pressure
+    }
+
+    /// Vorticity (z component of the curl) of every interior fluid cell.
+    pub fn curl(&self) -> Vec<f32> {
+        let n = self.size;
+        let mut curl = vec![0.0; n * n];
+        for j in 1..n - 1 {
+            for i in 1..n - 1 {
+                let index = index(n, i, j);
+                if self.solid[index] {
+                    continue;
+                }
+                curl[index] = 0.5
+                    * (self.vy[index + 1] - self.vy[index - 1] - self.vx[index + n]
+                        + self.vx[index - n]);
+            }
+        }
+        curl
+    }
+
+    /// Velocity divergence of every interior fluid cell, zero on the walls.
+    pub fn divergence(&self) -> Vec<f32> {
+        let n = self.size;
+        let mut div = vec![0.0; n * n];
+        for j in 1..n - 1 {
+            for i in 1..n - 1 {
+                if self.solid[index(n, i, j)] {
+                    continue;
+                }
+                div[index(n, i, j)] = 0.5
+                    * (self.vx[index(n, i + 1, j)] - self.vx[index(n, i - 1, j)]
+                        + self.vy[index(n, i, j + 1)]
+                        - self.vy[index(n, i, j - 1)]);
+            }
+        }
+        div
+    }
+}
+
+fn index(n: usize, x: usize, y: usize) -> usize {
+    let xc = x.min(n - 1);
+    let yc = y.min(n - 1);
+    xc + yc * n
+}
+
+fn fluid_mean(x: &[f32], solid: &[bool], cells: &[usize]) -> Option<f32> {
+    let mut sum = 0.0;
+    let mut count = 0;
+    for &c in cells {
+        if !solid[c] {
+            sum += x[c];
+            count += 1;
+        }
+    }
+    if count == 0 {
+        None
+    } else {
+        Some(sum / count as f32)
+    }
+}
+
+fn solid_value(g: Grid, b: usize, x: &[f32], index: usize) -> f32 {
+    let n = g.n;
+    let horizontal = [index - 1, index + 1];
+    let vertical = [index - n, index + n];
+    let all = [index - 1, index + 1, index - n, index + n];
+    let (across, along) = if b == 2 {
+        (vertical, horizontal)
+    } else {
+        (horizontal, vertical)
+    };
+    match (b, g.boundary) {
+        (0, _) => fluid_mean(x, g.solid, &all).unwrap_or(0.0),
+        (_, Boundary::NoSlip) => -fluid_mean(x, g.solid, &all).unwrap_or(0.0),
+        (_, Boundary::FreeSlip) => match fluid_mean(x, g.solid, &across) {
+            Some(v) => -v,
+            None => fluid_mean(x, g.solid, &along).unwrap_or(0.0),
+        },
+    }
+}
+
+fn set_bnd(g: Grid, b: usize, x: &mut [f32]) {
+    let n = g.n;
+    for &index in g.solid_cells {
+        x[index] = solid_value(g, b, x, index);
+    }
+    for i in 1..n - 1 {
+        x[index(n, i, 0)] = x[index(n, i, 1)] * (if b == 2 { -1.0 } else { 1.0 });
+        x[index(n, i, n - 1)] = x[index(n, i, n - 2)] * (if b == 2 { -1.0 } else { 1.0 });
+    }
+    for j in 1..n - 1 {
+        x[index(n, 0, j)] = x[index(n, 1, j)] * (if b == 1 { -1.0 } else { 1.0 });
+        x[index(n, n - 1, j)] = x[index(n, n - 2, j)] * (if b == 1 { -1.0 } else { 1.0 });
+    }
+    x[index(n, 0, 0)] = 0.5 * (x[index(n, 1, 0)] + x[index(n, 0, 1)]);
+    x[index(n, 0, n - 1)] = 0.5 * (x[index(n, 1, n - 1)] + x[index(n, 0, n - 2)]);
+    x[index(n, n - 1, 0)] = 0.5 * (x[index(n, n - 2, 0)] + x[index(n, n - 1, 1)]);
+    x[index(n, n - 1, n - 1)] = 0.5 * (x[index(n, n - 2, n - 1)] + x[index(n, n - 1, n - 2)]);
+}
+
+fn linear_solve(g: Grid, b: usize, x: &mut [f32], x0: &[f32], a: f32, c: f32) {
+    if g.parallel {
+        return linear_solve_par(g, b, x, x0, a, c);
+    }
+    let n = g.n;
+    let c_recip = 1.0 / c;
+    for _k in 0..g.iter {
+        for j in 1..n - 1 {
+            for i in 1..n - 1 {
+                let index = index(n, i, j);
+                if g.solid[index] {
+                    continue;
+                }
+                x[index] = (x0[index]
+                    + a * (x[index - 1] + x[index + 1] + x[index - n] + x[index + n]))
+                    * c_recip;
+            }
+        }
+        set_bnd(g, b, x);
+    }
+}
+
+fn jacobi_sweep(g: Grid, src: &[f32], dst: &mut [f32], x0: &[f32], a: f32, c_recip: f32) {
+    let n = g.n;
+    dst.par_chunks_mut(n)
+        .enumerate()
+        .skip(1)
+        .take(n - 2)
+        .for_each(|(j, row)| {
+            let base = j * n;
+            for i in 1..n - 1 {
+                let index = base + i;
+                row[i] = if g.solid[index] {
+                    src[index]
+                } else {
+                    (x0[index] + a * (src[index - 1] + src[index + 1] + src[index - n] + src[index + n]))
+                        * c_recip
+                };
+            }
+        });
+}
+
+fn linear_solve_par(g: Grid, b: usize, x: &mut [f32], x0: &[f32], a: f32, c: f32) {
+    let c_recip = 1.0 / c;
+    let mut next = x.to_vec();
+    for k in 0..g.iter {
+        if k % 2 == 0 {
+            jacobi_sweep(g, x, &mut next, x0, a, c_recip);
+            set_bnd(g, b, &mut next);
+        } else {
+            jacobi_sweep(g, &next, x, x0, a, c_recip);
+            set_bnd(g, b, x);
+        }
+    }
+    if g.iter % 2 == 1 {
+        x.copy_from_slice(&next);
+    }
+}
+
+fn diffuse(g: Grid, b: usize, x: &mut [f32], x0: &[f32], diff: f32, dt: f32) {
+    let n_minus_2 = (g.n - 2) as f32;
+    let a = dt * diff * n_minus_2 * n_minus_2;
+    linear_solve(g, b, x, x0, a, 1.0 + 4.0 * a);
+}
+
+fn project(g: Grid, vx: &mut [f32], vy: &mut [f32], p: &mut [f32], div: &mut [f32]) {
+    if g.parallel {
+        return project_par(g, vx, vy, p, div);
+    }
+    let n = g.n;
+    let h = 1.0 / (n - 2) as f32;
+    for j in 1..n - 1 {
+        for i in 1..n - 1 {
+            let index = index(n, i, j);
+            p[index] = 0.0;
+            if g.solid[index] {
+                div[index] = 0.0;
+                continue;
+            }
+            div[index] = -0.5 * h * (vx[index + 1] - vx[index - 1] + vy[index + n] - vy[index - n]);
+        }
+    }
+    set_bnd(g, 0, div);
+    set_bnd(g, 0, p);
+    linear_solve(g, 0, p, div, 1.0, 4.0);
+    for j in 1..n - 1 {
+        for i in 1..n - 1 {
+            let index = index(n, i, j);
+            if g.solid[index] {
+                continue;
+            }
+            vx[index] -= 0.5 * (p[index + 1] - p[index - 1]) / h;
+            vy[index] -= 0.5 * (p[index + n] - p[index - n]) / h;
+        }
+    }
+    set_bnd(g, 1, vx);
+    set_bnd(g, 2, vy);
+}
+
+fn project_par(g: Grid, vx: &mut [f32], vy: &mut [f32], p: &mut [f32], div: &mut [f32]) {
+    let n = g.n;
+    let h = 1.0 / (n - 2) as f32;
+    {
+        let (vx, vy) = (&*vx, &*vy);
+        div.par_chunks_mut(n)
+            .zip(p.par_chunks_mut(n))
+            .enumerate()
+            .skip(1)
+            .take(n - 2)
+            .for_each(|(j, (div_row, p_row))| {
+                let base = j * n;
+                for i in 1..n - 1 {
+                    let index = base + i;
+                    p_row[i] = 0.0;
+                    div_row[i] = if g.solid[index] {
+                        0.0
+                    } else {
+                        -0.5 * h * (vx[index + 1] - vx[index - 1] + vy[index + n] - vy[index - n])
+                    };
+                }
+            });
+    }
+    set_bnd(g, 0, div);
+    set_bnd(g, 0, p);
+    linear_solve(g, 0, p, div, 1.0, 4.0);
+    let p = &*p;
+    vx.par_chunks_mut(n)
+        .zip(vy.par_chunks_mut(n))
+        .enumerate()
+        .skip(1)
+        .take(n - 2)
+        .for_each(|(j, (vx_row, vy_row))| {
+            let base = j * n;
+            for i in 1..n - 1 {
+                let index = base + i;
+                if g.solid[index] {
+                    continue;
+                }
+                vx_row[i] -= 0.5 * (p[index + 1] - p[index - 1]) / h;
+                vy_row[i] -= 0.5 * (p[index + n] - p[index - n]) / h;
+            }
+        });
+    set_bnd(g, 1, vx);
+    set_bnd(g, 2, vy);
+}
+
+fn advect(g: Grid, b: usize, d: &mut [f32], d0: &[f32], vx: &[f32], vy: &[f32], dt: f32) {
+    if g.parallel {
+        return advect_par(g, b, d, d0, vx, vy, dt);
+    }
+    let n = g.n;
+    let dt0 = dt * (n - 2) as f32;
+    let max = n as f32 - 1.5;
+
+    for j in 1..n - 1 {
+        for i in 1..n - 1 {
+            let index = index(n, i, j);
+            if g.solid[index] {
+                continue;
+            }
+            let x = (i as f32 - dt0 * vx[index]).max(0.5).min(max);
+            let y = (j as f32 - dt0 * vy[index]).max(0.5).min(max);
+            let i0 = x.floor() as usize;
+            let j0 = y.floor() as usize;
+            let i1 = i0 + 1;
+            let j1 = j0 + 1;
+
+            let s1 = x - i0 as f32;
+            let s0 = 1.0 - s1;
+            let t1 = y - j0 as f32;
+            let t0 = 1.0 - t1;
+
+            d[index] = s0 * (t0 * d0[i0 + j0 * n] + t1 * d0[i0 + j1 * n])
+                + s1 * (t0 * d0[i1 + j0 * n] + t1 * d0[i1 + j1 * n]);
+        }
+    }
+    set_bnd(g, b, d);
+}
+
+fn advect_par(g: Grid, b: usize, d: &mut [f32], d0: &[f32], vx: &[f32], vy: &[f32], dt: f32) {
+    let n = g.n;
+    let dt0 = dt * (n - 2) as f32;
+    let max = n as f32 - 1.5;
+
+    d.par_chunks_mut(n)
+        .enumerate()
+        .skip(1)
+        .take(n - 2)
+        .for_each(|(j, row)| {
+            let base = j * n;
+            for i in 1..n - 1 {
+                let index = base + i;
+                if g.solid[index] {
+                    continue;
+                }
+                let x = (i as f32 - dt0 * vx[index]).max(0.5).min(max);
+                let y = (j as f32 - dt0 * vy[index]).max(0.5).min(max);
+                let i0 = x.floor() as usize;
+                let j0 = y.floor() as usize;
+                let s1 = x - i0 as f32;
+                let t1 = y - j0 as f32;
+                let k = i0 + j0 * n;
+
+                row[i] = (1.0 - s1) * ((1.0 - t1) * d0[k] + t1 * d0[k + n])
+                    + s1 * ((1.0 - t1) * d0[k + 1] + t1 * d0[k + n + 1]);
+            }
+        });
+    set_bnd(g, b, d);
+}
+
+impl FluidSolver for Fluid {
+    fn name(&self) -> &'static str {
+        "Stable fluids"
+    }
+
+    fn size(&self) -> usize {
+        Fluid::size(self)
+    }
+
+    fn channels(&self) -> usize {
+        Fluid::channels(self)
+    }
+
+    fn dye(&self, channel: usize) -> &[f32] {
+        Fluid::dye(self, channel)
+    }
+
+    fn add_dye(&mut self, x: usize, y: usize, channel: usize, amount: f32) {
+        Fluid::add_dye(self, x, y, channel, amount)
+    }
+
+    fn add_velocity(&mut self, x: usize, y: usize, x_amount: f32, y_amount: f32) {
+        Fluid::add_velocity(self, x, y, x_amount, y_amount)
+    }
+
+    fn add_temperature(&mut self, x: usize, y: usize, amount: f32) {
+        Fluid::add_temperature(self, x, y, amount)
+    }
+
+    fn velocity_at(&self, x: f32, y: f32) -> (f32, f32) {
+        Fluid::velocity_at(self, x, y)
+    }
+
+    fn curl(&self) -> Vec<f32> {
+        Fluid::curl(self)
+    }
+
+    fn divergence(&self) -> Vec<f32> {
+        Fluid::divergence(self)
+    }
+
+    fn pressure(&self) -> &[f32] {
+        Fluid::pressure(self)
+    }
+
+    fn solids(&self) -> &[bool] {
+        Fluid::solids(self)
+    }
+
+    fn set_solid(&mut self, x: usize, y: usize, solid: bool) {
+        Fluid::set_solid(self, x, y, solid)
+    }
+
+    fn clear_solids(&mut self) {
+        Fluid::clear_solids(self)
+    }
+
+    fn boundary(&self) -> Boundary {
+        Fluid::boundary(self)
+    }
+
+    fn set_boundary(&mut self, boundary: Boundary) {
+        Fluid::set_boundary(self, boundary)
+    }
+
+    fn vorticity(&self) -> f32 {
+        Fluid::vorticity(self)
+    }
+
+    fn set_vorticity(&mut self, vorticity: f32) {
+        Fluid::set_vorticity(self, vorticity)
+    }
+
+    fn buoyancy(&self) -> f32 {
+        Fluid::buoyancy(self)
+    }
+
+    fn set_buoyancy(&mut self, buoyancy: f32) {
+        Fluid::set_buoyancy(self, buoyancy)
+    }
+
+    fn ambient(&self) -> f32 {
+        Fluid::ambient(self)
+    }
+
+    fn set_ambient(&mut self, ambient: f32) {
+        Fluid::set_ambient(self, ambient)
+    }
+
+    fn cooling(&self) -> f32 {
+        Fluid::cooling(self)
+    }
+
+    fn set_cooling(&mut self, cooling: f32) {
+        Fluid::set_cooling(self, cooling)
+    }
+
+    fn decay(&mut self, amount: f32) {
+        Fluid::decay(self, amount)
+    }
+
+    fn clear(&mut self) {
+        Fluid::clear(self)
+    }
+
+    fn step(&mut self) {
+        Fluid::step(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Pcg32;
+    use std::f32::consts::PI;
+
+    const N: usize = 34;
+
+    fn grid<'a>(solid: &'a [bool], solid_cells: &'a [usize], iter: usize) -> Grid<'a> {
+        Grid {
+            n: N,
+            iter,
+            solid,
+            solid_cells,
+            boundary: Boundary::NoSlip,
+            parallel: false,
+        }
+    }
+
+    fn interior_sum(x: &[f32]) -> f32 {
+        let mut sum = 0.0;
+        for j in 1..N - 1 {
+            for i in 1..N - 1 {
+                sum += x[index(N, i, j)];
+            }
+        }
+        sum
+    }
+
+    fn rms_divergence(vx: &[f32], vy: &[f32]) -> f32 {
+        let mut sum = 0.0;
+        for j in 1..N - 1 {
+            for i in 1..N - 1 {
+                let k = index(N, i, j);
+                let div = 0.5 * (vx[k + 1] - vx[k - 1] + vy[k + N] - vy[k - N]);
+                sum += div * div;
+            }
+        }
+        (sum / ((N - 2) * (N - 2)) as f32).sqrt()
+    }
+
+    #[test]
+    fn advection_conserves_density_inside_walls() {
+        let solid = vec![false; N * N];
+        let g = grid(&solid, &[], 20);
+        // Swirl around the centre, made divergence free by the projection
+        let c = N as f32 * 0.5;
+        let mut vx = vec![0.0; N * N];
+        let mut vy = vec![0.0; N * N];
+        for j in 1..N - 1 {
+            for i in 1..N - 1 {
+                let k = index(N, i, j);
+                vx[k] = -(j as f32 - c) * 0.01;
+                vy[k] = (i as f32 - c) * 0.01;
+            }
+        }
+        let (mut p, mut div) = (vec![0.0; N * N], vec![0.0; N * N]);
+        project(g, &mut vx, &mut vy, &mut p, &mut div);
+
+        // Smooth blob away from the walls
+        let mut density = vec![0.0; N * N];
+        for j in 1..N - 1 {
+            for i in 1..N - 1 {
+                let (dx, dy) = (i as f32 - c, j as f32 - c + 4.0);
+                density[index(N, i, j)] = 100.0 * (-(dx * dx + dy * dy) / 16.0).exp();
+            }
+        }
+        let before = interior_sum(&density);
+        for _ in 0..20 {
+            let d0 = density.clone();
+            advect(g, 0, &mut density, &d0, &vx, &vy, 0.1);
+        }
+        let after = interior_sum(&density);
+        assert!((after - before).abs() / before < 0.05, "{} became {}", before, after);
+    }
+
+    /// Sum of a few random waves fading out at the walls, the centred
+    /// differences being blind to the checkerboard noise of independent cells.
+    fn random_waves(seed: u64) -> (Vec<f32>, Vec<f32>) {
+        let mut rng = Pcg32::new(seed);
+        let mut vx = vec![0.0; N * N];
+        let mut vy = vec![0.0; N * N];
+        for _ in 0..6 {
+            let (fx, fy) = (rng.range_f32(0.25, 1.0), rng.range_f32(0.25, 1.0));
+            let (ax, ay) = (rng.range_f32(-1.0, 1.0), rng.range_f32(-1.0, 1.0));
+            let phase = rng.range_f32(0.0, 2.0 * PI);
+            for j in 1..N - 1 {
+                for i in 1..N - 1 {
+                    let (x, y) = (i as f32 / N as f32, j as f32 / N as f32);
+                    let k = index(N, i, j);
+                    let fade = ((PI * x).sin() * (PI * y).sin()).powi(2);
+                    vx[k] += fade * ax * (2.0 * PI * (fx * x + fy * y) + phase).sin();
+                    vy[k] += fade * ay * (2.0 * PI * (fy * x - fx * y) + phase).cos();
+                }
+            }
+        }
+        (vx, vy)
+    }
+
+    /// Divergence left by projecting the waves of `seed`, before and after.
+    fn projected_divergence(g: Grid, seed: u64) -> (f32, f32) {
+        let (mut vx, mut vy) = random_waves(seed);
+        set_bnd(g, 1, &mut vx);
+        set_bnd(g, 2, &mut vy);
+        let before = rms_divergence(&vx, &vy);
+        let (mut p, mut div) = (vec![0.0; N * N], vec![0.0; N * N]);
+        project(g, &mut vx, &mut vy, &mut p, &mut div);
+        (before, rms_divergence(&vx, &vy))
+    }
+
+    #[test]
+    fn projection_removes_divergence() {
+        let solid = vec![false; N * N];
+        let (before, after) = projected_divergence(grid(&solid, &[], 500), 7);
+        assert!(after < before * 0.05, "divergence {} became {}", before, after);
+    }
+
+    #[test]
+    fn parallel_projection_matches_the_serial_one() {
+        // Jacobi needs about twice the Gauss-Seidel iterations
+        let solid = vec![false; N * N];
+        let (_, serial) = projected_divergence(grid(&solid, &[], 500), 7);
+        let parallel = Grid {
+            parallel: true,
+            ..grid(&solid, &[], 1000)
+        };
+        let (before, after) = projected_divergence(parallel, 7);
+        assert!(after < serial * 1.1, "parallel left {}, serial {}", after, serial);
+        assert!(after < before * 0.05, "divergence {} became {}", before, after);
+    }
+
+    #[test]
+    fn parallel_advection_matches_the_serial_one() {
+        let mut solid = vec![false; N * N];
+        let mut cells = Vec::new();
+        for j in 12..16 {
+            for i in 20..24 {
+                solid[index(N, i, j)] = true;
+                cells.push(index(N, i, j));
+            }
+        }
+        let g = grid(&solid, &cells, 20);
+        let (mut vx, mut vy) = random_waves(3);
+        for v in vx.iter_mut().chain(vy.iter_mut()) {
+            *v *= 0.2;
+        }
+        let mut rng = Pcg32::new(4);
+        let d0: Vec<f32> = (0..N * N).map(|_| rng.range_f32(0.0, 100.0)).collect();
+
+        let mut serial = vec![0.0; N * N];
+        advect(g, 0, &mut serial, &d0, &vx, &vy, 0.1);
+        let mut parallel = vec![0.0; N * N];
+        advect_par(g, 0, &mut parallel, &d0, &vx, &vy, 0.1);
+        let bits = |d: &[f32]| d.iter().map(|v| v.to_bits()).collect::<Vec<u32>>();
+        assert_eq!(bits(&serial), bits(&parallel));
+    }
+}
@@ -0,0 +1,421 @@
+use crate::ant::{Ant, Turn};
+use std::str::FromStr;
+
+/// What a turmite in `state` does on a cell: recolour it, turn and switch state.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transition {
+    pub write: u8,
+    pub turn: Turn,
+    pub next_state: usize,
+}
+
+/// Transition table indexed by turmite state and cell colour.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rule {
+    colors: usize,
+    states: usize,
+    table: Vec<Transition>,
+}
+
+impl Rule {
+    /// Single state ant rule such as `RL` (Langton's ant) or `LLRR`: on
+    /// colour `c` the ant turns by the `c`-th letter and paints `c + 1`.
+    pub fn from_turns(turns: &str) -> Result<Rule, String> {
+        let turns = turns
+            .trim()
+            .chars()
+            .map(|c| Turn::from_char(c).ok_or_else(|| format!("unknown turn {:?}", c)))
+            .collect::<Result<Vec<Turn>, String>>()?;
+        let colors = turns.len();
+        if colors < 2 || colors > 256 {
+            return Err(format!("rule needs 2 to 256 colours, got {}", colors));
+        }
+        let table = turns
+            .iter()
+            .enumerate()
+            .map(|(c, &turn)| Transition {
+                write: ((c + 1) % colors) as u8,
+                turn,
+                next_state: 0,
+            })
+            .collect();
+        Ok(Rule { colors, states: 1, table })
+    }
+
+    /// Multi-state table, `table[state][color]`.
+    pub fn from_table(table: Vec<Vec<Transition>>) -> Result<Rule, String> {
+        let states = table.len();
+        let colors = table.first().map_or(0, |row| row.len());
+        if states == 0 || colors < 2 || colors > 256 {
+            return Err("table needs at least one state and 2 to 256 colours".to_string());
+        }
+        for (state, row) in table.iter().enumerate() {
+            if row.len() != colors {
+                return Err(format!("state {} has {} colours, expected {}", state, row.len(), colors));
+            }
+            for t in row.iter() {
+                if t.write as usize >= colors || t.next_state >= states {
+                    return Err(format!("state {} has an out of range transition {:?}", state, t));
+                }
+            }
+        }
+        Ok(Rule {
+            colors,
+            states,
+            table: table.into_iter().flatten().collect(),
+        })
+    }
+
+    /// Reads a table in the `{{{write, turn, next}, ...}, ...}` notation used
+    /// by Ed Pegg Jr., turns being coded 1 none, 2 right, 4 u-turn, 8 left.
+    pub fn from_pegg(text: &str) -> Result<Rule, String> {
+        let mut table = Vec::new();
+        let mut row = Vec::new();
+        let mut numbers = Vec::new();
+        let mut number = String::new();
+        let mut depth = 0;
+        for c in text.chars().chain(std::iter::once(' ')) {
+            if c.is_ascii_digit() {
+                number.push(c);
+                continue;
+            }
+            if !number.is_empty() {
+                numbers.push(number.parse::<usize>().map_err(|e| e.to_string())?);
+                number.clear();
+            }
+            match c {
+                '{' => depth += 1,
+                '}' if depth > 0 => {
+                    match depth {
+                        3 => {
+                            if numbers.len() != 3 {
+                                return Err(format!("transition needs 3 numbers, got {:?}", numbers));
+                            }
+                            let turn = match numbers[1] {
+                                1 => Turn::None,
+                                2 => Turn::Right,
+                                4 => Turn::UTurn,
+                                8 => Turn::Left,
+                                other => return Err(format!("unknown turn code {}", other)),
+                            };
+                            row.push(Transition {
+                                write: numbers[0].min(255) as u8,
+                                turn,
+                                next_state: numbers[2],
+                            });
+                            numbers.clear();
+                        }
+                        2 => table.push(std::mem::take(&mut row)),
+                        _ => {}
+                    }
+                    depth -= 1;
+                }
+                '}' => return Err("unbalanced '}' in table".to_string()),
+                ',' | ' ' | '\t' | '\n' | '\r' => {}
+                other => return Err(format!("unexpected {:?} in table", other)),
+            }
+        }
+        if depth != 0 {
+            return Err("unbalanced '{' in table".to_string());
+        }
+        Rule::from_table(table)
+    }
+
+    pub fn colors(&self) -> usize {
+        self.colors
+    }
+
+    pub fn states(&self) -> usize {
+        self.states
+    }
+
+    pub fn transition(&self, state: usize, color: u8) -> Transition {
+        self.table[state * self.colors + color as usize]
+    }
+}
+
+impl FromStr for Rule {
+    type Err = String;
+
+    /// Accepts both turn strings and Pegg tables.
+    fn from_str(s: &str) -> Result<Rule, String> {
+        if s.trim_start().starts_with('{') {
+            Rule::from_pegg(s)
+        } else {
+            Rule::from_turns(s)
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Edges {
+    /// Ants leaving one side come back on the opposite one.
+    Toroidal,
+    /// Ants halt on the edge they would cross.
+    Bounded,
+}
+
+/// Grid of colours shared by any number of turmites. Row 0 is at the bottom
+/// and every cell starts with colour 0.
+pub struct TurmiteGrid {
+    width: usize,
+    height: usize,
+    cells: Vec<u8>,
+    rule: Rule,
+    ants: Vec<Ant>,
+    edges: Edges,
+    steps: u64,
+}
+
+impl TurmiteGrid {
+    pub fn new(width: usize, height: usize, rule: Rule, edges: Edges) -> Self {
+        TurmiteGrid {
+            width,
+            height,
+            cells: vec![0; width * height],
+            rule,
+            ants: Vec::new(),
+            edges,
+            steps: 0,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn index(&self, x: usize, y: usize) -> usize {
+        x + y * self.width
+    }
+
+    pub fn cells(&self) -> &[u8] {
+        &self.cells
+    }
+
+    pub fn cell(&self, x: usize, y: usize) -> u8 {
+        self.cells[self.index(x, y)]
+    }
+
+    pub fn rule(&self) -> &Rule {
+        &self.rule
+    }
+
+    pub fn ants(&self) -> &[Ant] {
+        &self.ants
+    }
+
+    pub fn add_ant(&mut self, ant: Ant) {
+        assert!(ant.x < self.width && ant.y < self.height, "ant outside the grid");
+        assert!(ant.state < self.rule.states(), "ant state outside the rule");
+        self.ants.push(ant);
+    }
+
+    pub fn edges(&self) -> Edges {
+        self.edges
+    }
+
+    pub fn set_edges(&mut self, edges: Edges) {
+        self.edges = edges;
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Moves every ant once, in the order they were added.
+    pub fn step(&mut self) {
+        let (w, h) = (self.width as i64, self.height as i64);
+        for ant in self.ants.iter_mut() {
+            if ant.halted {
+                continue;
+            }
+            let index = ant.x + ant.y * self.width;
+            let t = self.rule.transition(ant.state, self.cells[index]);
+            self.cells[index] = t.write;
+            ant.head = ant.head.turn(t.turn);
+            ant.state = t.next_state;
+
+            let (dx, dy) = ant.head.delta();
+            let (x, y) = (ant.x as i64 + dx, ant.y as i64 + dy);
+            match self.edges {
+                Edges::Toroidal => {
+                    ant.x = x.rem_euclid(w) as usize;
+                    ant.y = y.rem_euclid(h) as usize;
+                }
+                Edges::Bounded => {
+                    if x < 0 || y < 0 || x >= w || y >= h {
+                        ant.halted = true;
+                    } else {
+                        ant.x = x as usize;
+                        ant.y = y as usize;
+                    }
+                }
+            }
+        }
+        self.steps += 1;
+    }
+
+    pub fn run(&mut self, steps: u64) {
+        for _ in 0..steps {
+            self.step();
+        }
+    }
+}
+
+/// Display colour of a cell, colour 0 white and the others fading to black.
+pub fn cell_rgb(color: u8, colors: usize) -> [u8; 3] {
+    let v = 255 - (color as usize * 255 / (colors - 1).max(1)) as u8;
+    [v, v, v]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ant::Direction;
+
+    fn langton(width: usize, height: usize, edges: Edges) -> TurmiteGrid {
+        TurmiteGrid::new(width, height, Rule::from_turns("RL").unwrap(), edges)
+    }
+
+    fn black_cells(grid: &TurmiteGrid) -> usize {
+        grid.cells().iter().filter(|&&c| c == 1).count()
+    }
+
+    #[test]
+    fn langtons_ant_walks_a_square_first() {
+        let mut grid = langton(11, 11, Edges::Toroidal);
+        grid.add_ant(Ant::new(5, 5, Direction::U));
+        let expected = [
+            (6, 5, Direction::R),
+            (6, 4, Direction::D),
+            (5, 4, Direction::L),
+            (5, 5, Direction::U),
+            (4, 5, Direction::L),
+        ];
+        for &(x, y, head) in expected.iter() {
+            grid.step();
+            let ant = grid.ants()[0];
+            assert_eq!((ant.x, ant.y, ant.head), (x, y, head));
+        }
+        // Back on its first cell, the ant turned left and cleared it
+        assert_eq!(grid.cell(5, 5), 0);
+        assert_eq!(black_cells(&grid), 3);
+    }
+
+    #[test]
+    fn langtons_ant_builds_its_highway() {
+        let mut grid = langton(200, 200, Edges::Toroidal);
+        grid.add_ant(Ant::new(100, 100, Direction::U));
+        grid.run(10_000);
+        assert_eq!(black_cells(&grid), 720);
+
+        // Once on the highway every 104 steps add 12 cells and move the ant
+        // two cells diagonally
+        grid.run(1000);
+        for _ in 0..5 {
+            let (before, ant) = (black_cells(&grid), grid.ants()[0]);
+            grid.run(104);
+            let moved = grid.ants()[0];
+            assert_eq!(black_cells(&grid), before + 12);
+            assert_eq!((moved.x + 2, moved.y + 2, moved.head), (ant.x, ant.y, ant.head));
+        }
+    }
+
+    #[test]
+    fn turn_strings_paint_the_next_colour() {
+        let rule = Rule::from_turns(" lLrR\n").unwrap();
+        assert_eq!((rule.colors(), rule.states()), (4, 1));
+        let turns = [Turn::Left, Turn::Left, Turn::Right, Turn::Right];
+        for (c, &turn) in turns.iter().enumerate() {
+            let expected = Transition {
+                write: ((c + 1) % 4) as u8,
+                turn,
+                next_state: 0,
+            };
+            assert_eq!(rule.transition(0, c as u8), expected);
+        }
+        assert!(Rule::from_turns("R").is_err());
+        assert!(Rule::from_turns("RX").is_err());
+        assert!(Rule::from_turns(&"R".repeat(257)).is_err());
+        assert_eq!("RL".parse::<Rule>(), Rule::from_turns("RL"));
+    }
+
+    fn t(write: u8, turn: Turn, next_state: usize) -> Transition {
+        Transition { write, turn, next_state }
+    }
+
+    #[test]
+    fn pegg_tables_read_as_the_same_rule() {
+        // Fibonacci spiral turmite, two states and two colours
+        let text = "{{{1, 8, 1}, {1, 8, 1}}, {{1, 2, 1}, {0, 1, 0}}}";
+        let table = vec![
+            vec![t(1, Turn::Left, 1), t(1, Turn::Left, 1)],
+            vec![t(1, Turn::Right, 1), t(0, Turn::None, 0)],
+        ];
+        let rule = Rule::from_pegg(text).unwrap();
+        assert_eq!(rule, Rule::from_table(table.clone()).unwrap());
+        assert_eq!(text.parse::<Rule>(), Ok(rule.clone()));
+        for (state, row) in table.iter().enumerate() {
+            for (color, &transition) in row.iter().enumerate() {
+                assert_eq!(rule.transition(state, color as u8), transition);
+            }
+        }
+
+        // A single state table is the same as its turn string
+        let langton = Rule::from_pegg("{{{1, 2, 0}, {0, 8, 0}}}").unwrap();
+        assert_eq!(langton, Rule::from_turns("RL").unwrap());
+        assert_eq!(Rule::from_pegg("{{{1,4,0},{0,1,0}}}"), Rule::from_turns("UN"));
+    }
+
+    #[test]
+    fn malformed_tables_are_rejected() {
+        let bad = [
+            "{{{1, 2}, {0, 8, 0}}}",
+            "{{{1, 3, 0}, {0, 8, 0}}}",
+            "{{{1, 2, 0}, {0, 8, 0}}",
+            "{{{1, 2, 0}, {0, 8, 0}}}}",
+            "{{{1, 2, 0}; {0, 8, 0}}}",
+            "{{{1, 2, 0}, {0, 8, 0}}, {{1, 2, 0}}}",
+            "{{{2, 2, 0}, {0, 8, 0}}}",
+            "{{{1, 2, 1}, {0, 8, 0}}}",
+            "{{{1, 2, 0}}}",
+            "",
+        ];
+        for text in bad.iter() {
+            assert!(Rule::from_pegg(text).is_err(), "{:?} was accepted", text);
+        }
+        assert!(Rule::from_table(Vec::new()).is_err());
+        assert!(Rule::from_table(vec![vec![t(0, Turn::Left, 0), t(2, Turn::Left, 0)]]).is_err());
+        assert!(Rule::from_table(vec![vec![t(0, Turn::Left, 0), t(1, Turn::Left, 1)]]).is_err());
+    }
+
+    #[test]
+    fn toroidal_ants_wrap_around() {
+        // In the bottom right corner the ant turns right across the right
+        // edge, then down across the bottom one
+        let mut grid = langton(4, 3, Edges::Toroidal);
+        grid.add_ant(Ant::new(3, 0, Direction::U));
+        grid.step();
+        assert_eq!(grid.ants()[0], Ant::new(0, 0, Direction::R));
+        grid.step();
+        assert_eq!(grid.ants()[0], Ant::new(0, 2, Direction::D));
+    }
+
+    #[test]
+    fn bounded_ants_halt_on_the_edge() {
+        let mut grid = langton(4, 3, Edges::Bounded);
+        grid.add_ant(Ant::new(3, 0, Direction::U));
+        grid.step();
+        let halted = grid.ants()[0];
+        assert_eq!((halted.x, halted.y, halted.head, halted.halted), (3, 0, Direction::R, true));
+        // A halted ant neither moves nor paints
+        grid.run(3);
+        assert_eq!(grid.ants()[0], halted);
+        assert_eq!(black_cells(&grid), 1);
+        assert_eq!(grid.steps(), 4);
+    }
+}